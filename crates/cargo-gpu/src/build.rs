@@ -3,18 +3,108 @@
 use anyhow::Context as _;
 use clap::Parser;
 use spirv_builder_cli::{Linkage, ShaderModule};
+use std::io::Write as _;
 
-use crate::{install::Install, target_spec_dir};
+use crate::{install::Install, reflect::SpirvModule, spirv_cli::SpirvCli, target_spec_dir};
+
+/// The layout of the entries in the shader manifest.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ManifestSchema {
+    /// A list of `{ "source_path", "entry_point", "wgsl_entry_point" }` objects.
+    #[default]
+    Linkage,
+    /// A list of `{ "entry", "path" }` objects, the layout of the older `build-manifest.json`.
+    ShaderModule,
+    /// A single `{ "<entry_point>": "<source_path>" }` object.
+    Map,
+    /// A single `{ "<entry_point>": { "source_path", "entry_point", "wgsl_entry_point" } }`
+    /// object, which keeps all of the fields of `linkage` but can be looked up by entry point.
+    LinkageMap,
+}
+
+/// The separator between the components of the manifest's shader paths.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathSeparator {
+    /// The separator of the platform that `cargo gpu` runs on.
+    Auto,
+    /// A forward slash, which works on all platforms.
+    #[default]
+    Slash,
+    /// A backslash, for Windows tools that expect native paths.
+    Backslash,
+}
+
+impl PathSeparator {
+    /// The separator as a string.
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => std::path::MAIN_SEPARATOR_STR,
+            Self::Slash => "/",
+            Self::Backslash => "\\",
+        }
+    }
+}
+
+/// How `cargo gpu build` reports what it's doing.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Messages for people, prefixed with a crab.
+    #[default]
+    Human,
+    /// A JSON object per line on stdout, for build tools, like `cargo build --message-format json`.
+    /// Everything else, including the output of the compiler, goes to stderr.
+    Json,
+}
+
+/// A line of `--message-format json` output.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum Message {
+    /// An entry point was compiled.
+    CompiledEntryPoint {
+        /// The entry point's name.
+        entry_point: String,
+        /// The shader crate that the entry point is in.
+        shader_crate: std::path::PathBuf,
+        /// The compiled module that contains the entry point.
+        spv_path: std::path::PathBuf,
+    },
+    /// The build finished, always the last message.
+    BuildFinished {
+        /// Whether every shader crate compiled.
+        success: bool,
+    },
+}
+
+impl Message {
+    /// Print the message as a line of JSON.
+    #[expect(
+        clippy::print_stdout,
+        reason = "The output is intended to be read by tools, so no crab prefix"
+    )]
+    fn emit(&self) -> anyhow::Result<()> {
+        println!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
 
 /// `cargo build` subcommands
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each bool is an independent command line flag"
+)]
 pub struct Build {
     /// Install the `rust-gpu` compiler and components
     #[clap(flatten)]
     pub install: Install,
 
     /// Shader target.
-    #[clap(long, default_value = "spirv-unknown-vulkan1.2")]
+    #[clap(
+        long,
+        default_value = "spirv-unknown-vulkan1.2",
+        env = "CARGO_GPU_SHADER_TARGET"
+    )]
     shader_target: String,
 
     /// Set cargo default-features.
@@ -26,14 +116,452 @@ pub struct Build {
     features: Vec<String>,
 
     /// Path to the output directory for the compiled shaders.
-    #[clap(long, short, default_value = "./")]
+    #[clap(long, short, default_value = "./", env = "CARGO_GPU_OUTPUT_DIR")]
     pub output_dir: std::path::PathBuf,
+
+    #[expect(
+        clippy::option_option,
+        reason = "Distinguishes between the flag being absent and the flag being given without a value"
+    )]
+    /// Write the manifest's shader paths relative to the given directory, instead of relative
+    /// to the shader crate.
+    ///
+    /// When no directory is given, `OUT_DIR` is used, which cargo sets when running build
+    /// scripts. This allows a `build.rs` to resolve the manifest's paths from `OUT_DIR`.
+    #[clap(long, num_args(0..=1), value_name = "DIR")]
+    pub manifest_relative_to_out_dir: Option<Option<std::path::PathBuf>>,
+
+    /// The layout of the entries in the shader manifest.
+    #[clap(long, value_enum, default_value_t)]
+    pub manifest_schema: ManifestSchema,
+
+    /// The separator between the components of the manifest's shader paths. Forward slashes
+    /// work on all platforms, but some Windows tools expect backslashes.
+    #[clap(long, value_enum, default_value_t)]
+    pub path_separator: PathSeparator,
+
+    /// Compile every shader crate matching this glob pattern, eg "shaders/*", instead of a
+    /// single `--shader-crate`.
+    ///
+    /// The pattern is relative to the root of the current cargo workspace. Matches that are
+    /// not shader crates (a crate depending on `spirv-std`) are skipped. Each crate's shaders and
+    /// manifest are written to a subdirectory of `--output-dir` named after the crate's directory.
+    #[clap(long, conflicts_with = "shader_crates")]
+    pub shader_crate_glob: Option<String>,
+
+    /// When compiling several shader crates, stop at the first one that fails to compile,
+    /// rather than compiling the others and reporting all the failures at the end.
+    #[clap(long)]
+    pub fail_fast: bool,
+
+    /// Record the SPIR-V capabilities used by each entry point in the manifest, so that
+    /// pipelines can request the minimal set of capabilities they need.
+    ///
+    /// The capabilities are read from the compiled modules. When all entry points are compiled
+    /// into a single module, they all share that module's capabilities.
+    #[clap(long)]
+    pub reflect_capabilities: bool,
+
+    /// Record the local workgroup size of each compute entry point in the manifest, as its
+    /// `workgroup_size`, so that it can be dispatched without parsing its module.
+    ///
+    /// The sizes are read from the `LocalSize` execution modes of the compiled modules.
+    #[clap(long)]
+    pub reflect_workgroup_sizes: bool,
+
+    /// Only write this entry point's module, and its entry in the manifest. Can be given more
+    /// than once.
+    ///
+    /// The whole shader crate is still compiled, but the other modules aren't copied to
+    /// `--output-dir`. An entry point that the shader crate doesn't have is an error.
+    #[clap(long = "entry-point", value_name = "ENTRY_POINT")]
+    pub entry_point_filter: Vec<String>,
+
+    /// The extension of the compiled modules written to `--output-dir`, and so of the paths in
+    /// the manifest, for tools that expect eg ".spirv" or ".bin".
+    #[clap(long, default_value = "spv", value_name = "EXTENSION")]
+    pub spv_output_extension: String,
+
+    /// Hard-link the compiled modules into `--output-dir` instead of copying them, saving the
+    /// disk space and time of a copy. Modules are still copied when linking fails, eg as the
+    /// output dir is on another filesystem.
+    ///
+    /// The links share their contents with the modules in the shader crate's target dir, so
+    /// anything modifying those in place also modifies the output.
+    #[clap(long)]
+    pub link_artifacts: bool,
+
+    /// The `opt-level` of the shader crate's compilation, eg "0" to debug a miscompilation.
+    /// Defaults to the shader crate's own `[profile.release]`, as `spirv-builder` compiles in
+    /// release mode.
+    ///
+    /// This only affects `rustc`'s optimisations before the `rust-gpu` backend, and is passed
+    /// to cargo as `CARGO_PROFILE_RELEASE_OPT_LEVEL`, so it also applies to the shader crate's
+    /// dependencies.
+    #[clap(long, value_parser = ["0", "1", "2", "3", "s", "z"], value_name = "LEVEL")]
+    pub shader_opt_level: Option<String>,
+
+    /// Compile all entry points into a single SPIR-V module, rather than one module per entry
+    /// point. Every entry point in the manifest then refers to that one module.
+    #[clap(long)]
+    pub single_module: bool,
+
+    /// Record the `--shader-target` that each entry point was compiled for in the manifest, so
+    /// that manifests of builds for different targets can be told apart, or merged.
+    #[clap(long)]
+    pub manifest_target: bool,
+
+    /// Separator that replaces each `::` of an entry point's module path when mangling it into
+    /// its `wgsl_entry_point`. By default the `::`s are removed.
+    ///
+    /// Distinct entry points that mangle to the same name are an error.
+    #[clap(long, value_parser = parse_entry_name_separator)]
+    pub entry_name_separator: Option<String>,
+
+    /// After building, print a summary of each compiled SPIR-V module's header and the
+    /// capabilities and extensions that it declares.
+    #[clap(long)]
+    pub dump_spirv_metadata: bool,
+
+    /// Enable a SPIR-V capability, eg "Int8", on top of the ones implied by the target.
+    #[clap(long, value_parser = parse_capability)]
+    pub capabilities: Vec<spirv::Capability>,
+
+    /// After building, report each of the `--capabilities` that no compiled module declares.
+    ///
+    /// Unused capabilities are harmless, but can point to a mistaken target or a stale config.
+    #[clap(long)]
+    pub report_unused_capabilities: bool,
+
+    /// Experimental: when compiling fails because of missing capabilities, enable the
+    /// capabilities that the errors name and compile again, up to a few times.
+    ///
+    /// The errors are matched heuristically, so add the reported capabilities to
+    /// `--capabilities` once they're known.
+    #[clap(long)]
+    pub auto_capabilities: bool,
+
+    /// Also write a Rust source file declaring a `pub const <ENTRY_POINT>: &str` with the
+    /// manifest path of each entry point's module, for a crate to `include!`.
+    ///
+    /// A relative path is relative to `--output-dir`, next to `manifest.json`. Entry points
+    /// are converted to upper snake case identifiers, and distinct entry points that convert to
+    /// the same identifier are an error.
+    #[clap(long, value_name = "FILE")]
+    pub emit_rust_symbols: Option<std::path::PathBuf>,
+
+    /// Also write a JSON summary of the build, with its duration, the sizes of the compiled
+    /// modules and the resolved `rust-gpu` source, toolchain and target, eg for CI dashboards.
+    ///
+    /// A relative path is relative to `--output-dir`. The manifest is unaffected.
+    #[clap(long, value_name = "PATH")]
+    pub summary_json: Option<std::path::PathBuf>,
+
+    /// After building, print the path of each compiled SPIR-V module on its own line, for shell
+    /// scripts and Makefiles. Combine with `--quiet` so that stdout only has the paths.
+    #[clap(long)]
+    pub print_spv_paths: bool,
+
+    /// How to report what the build is doing. `json` prints a JSON object per line to stdout
+    /// for each compiled entry point, then one once the build finishes, and hides the human
+    /// messages.
+    #[clap(long, value_enum, default_value_t)]
+    pub message_format: MessageFormat,
+
+    /// Whether stdout is reserved for the output of another command, like the names printed by
+    /// `cargo gpu show entry-points`, so that the compiler's output goes to stderr instead.
+    #[clap(skip)]
+    is_stdout_reserved: bool,
+
+    /// Only check that the build could start, without installing or compiling anything: the
+    /// arguments and metadata are merged, each shader crate's `rust-gpu` source and toolchain
+    /// are resolved, and the target is checked against the available target specs.
+    ///
+    /// The first problem found is reported as an error, which makes this a quick check for
+    /// editors and pre-commit hooks.
+    #[clap(long)]
+    pub check_only: bool,
+}
+
+/// The stats of a shader crate's build written by `--summary-json`.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct BuildSummary {
+    /// The shader crate that was built.
+    pub shader_crate: std::path::PathBuf,
+    /// The `rust-gpu` source that the shader crate was built with.
+    pub source: String,
+    /// The toolchain channel that the shader crate was built with.
+    pub channel: String,
+    /// The target that the shader crate was built for.
+    pub target: String,
+    /// How long the build took in seconds, including installing the `rust-gpu` backend.
+    pub duration_secs: f64,
+    /// The number of entry points in the manifest.
+    pub entry_point_count: usize,
+    /// The total size in bytes of the compiled modules.
+    pub total_size: u64,
+    /// Each compiled module, in the order of their paths.
+    pub modules: Vec<ModuleSummary>,
+}
+
+/// A compiled module in a [`BuildSummary`].
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSummary {
+    /// The module's path, as it is in the manifest.
+    pub path: String,
+    /// The module's size in bytes.
+    pub size: u64,
+}
+
+/// Parse a capability from its name in the SPIR-V spec.
+fn parse_capability(name: &str) -> Result<spirv::Capability, String> {
+    name.parse()
+        .map_err(|()| format!("'{name}' is not a SPIR-V capability"))
+}
+
+/// Parse an `--entry-name-separator`, which can't be empty, as that's what leaving it out
+/// means, nor contain a path separator, as the mangled names aren't paths.
+fn parse_entry_name_separator(separator: &str) -> Result<String, String> {
+    if separator.is_empty() {
+        return Err("leave out `--entry-name-separator` to remove the `::`s".to_owned());
+    }
+    if separator.contains(['/', '\\']) {
+        return Err(format!("'{separator}' contains a path separator"));
+    }
+    Ok(separator.to_owned())
 }
 
 impl Build {
     /// Entrypoint
     pub fn run(&mut self) -> anyhow::Result<()> {
-        let (dylib_path, spirv_builder_cli_path) = self.install.run()?;
+        if self.message_format == MessageFormat::Human {
+            return self.build();
+        }
+
+        // The human messages would break up the JSON lines.
+        self.install.is_quiet = true;
+        let result = self.build();
+        Message::BuildFinished {
+            success: result.is_ok(),
+        }
+        .emit()?;
+        result
+    }
+
+    /// Compile the shader crate, or all of the shader crates.
+    fn build(&mut self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.install.only_toolchain,
+            "`--install-only-toolchain` can only be used with `cargo gpu install`, \
+             as building shaders needs the full `rust-gpu` backend"
+        );
+        anyhow::ensure!(
+            !self.install.spirv_source_only,
+            "`--spirv-source-only` can only be used with `cargo gpu install`"
+        );
+        anyhow::ensure!(
+            !self.install.print_toolchain_install_commands,
+            "`--print-toolchain-install-commands` can only be used with `cargo gpu install`"
+        );
+        anyhow::ensure!(
+            !self.install.dry_run,
+            "`--dry-run` can only be used with `cargo gpu install`, see `--check-only` for builds"
+        );
+
+        let shader_crates = self.shader_crates()?;
+        if self.check_only {
+            return self.check(&shader_crates);
+        }
+        if self.shader_crate_glob.is_none() && shader_crates.len() <= 1 {
+            return self.build_shader_crate();
+        }
+        self.build_shader_crates(&shader_crates)
+    }
+
+    /// The shader crates to compile, from `--shader-crate-glob` or `--shader-crate`.
+    fn shader_crates(&self) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        let Some(pattern) = &self.shader_crate_glob else {
+            if self.install.shader_crates.is_empty() {
+                return Ok(vec![self.install.shader_crate().to_path_buf()]);
+            }
+            return Ok(self.install.shader_crates.clone());
+        };
+        let workspace_root = crate::cargo_workspace_root(&std::env::current_dir()?)?;
+        let shader_crates = expand_shader_crate_glob(&workspace_root, pattern)?;
+        anyhow::ensure!(
+            !shader_crates.is_empty(),
+            "no shader crates match '{pattern}' in workspace '{}'",
+            workspace_root.display()
+        );
+        Ok(shader_crates)
+    }
+
+    /// For `--check-only`, check that the target has a spec and resolve the `rust-gpu` source
+    /// and toolchain of each shader crate, without installing anything.
+    fn check(&self, shader_crates: &[std::path::PathBuf]) -> anyhow::Result<()> {
+        self.ensure_target_is_available()?;
+        for shader_crate in shader_crates {
+            anyhow::ensure!(
+                shader_crate.exists(),
+                "shader crate '{}' does not exist. (Current dir is '{}')",
+                shader_crate.display(),
+                std::env::current_dir()?.display()
+            );
+            let spirv_cli = self.install.spirv_cli(shader_crate).with_context(|| {
+                format!(
+                    "could not resolve the `rust-gpu` source of shader crate '{}'",
+                    shader_crate.display()
+                )
+            })?;
+            if !self.install.is_quiet {
+                crate::user_output!(
+                    "Shader crate {} would be compiled for {} with {spirv_cli}\n",
+                    shader_crate.display(),
+                    self.shader_target
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile several shader crates, each into a subdirectory of `--output-dir` named after the
+    /// crate's directory. A failed crate doesn't stop the others unless `--fail-fast` is given.
+    fn build_shader_crates(&self, shader_crates: &[std::path::PathBuf]) -> anyhow::Result<()> {
+        let mut output_dirs = std::collections::BTreeMap::new();
+        for shader_crate in shader_crates {
+            let name = shader_crate
+                .canonicalize()
+                .ok()
+                .and_then(|path| path.file_name().map(ToOwned::to_owned))
+                .with_context(|| {
+                    format!(
+                        "Couldn't get the directory name of the shader crate '{}'",
+                        shader_crate.display()
+                    )
+                })?;
+            if let Some(other) = output_dirs.insert(name.clone(), shader_crate) {
+                anyhow::bail!(
+                    "shader crates '{}' and '{}' would both be written to '{}'",
+                    other.display(),
+                    shader_crate.display(),
+                    self.output_dir.join(name).display()
+                );
+            }
+        }
+
+        let mut failed = Vec::new();
+        let mut installs = Installs::new();
+        for (name, shader_crate) in output_dirs {
+            let mut build = self.clone();
+            build.shader_crate_glob = None;
+            build.output_dir = self.output_dir.join(name);
+            build.install.shader_crates = vec![shader_crate.clone()];
+            if let Err(error) = build.build_shader_crate_with(&mut installs) {
+                if self.fail_fast {
+                    return Err(error);
+                }
+                log::error!("{error:?}");
+                if !self.install.is_quiet {
+                    crate::user_output!(
+                        "Failed to compile shader crate {}: {error}\n",
+                        shader_crate.display()
+                    );
+                }
+                failed.push(shader_crate.display().to_string());
+            }
+        }
+        anyhow::ensure!(
+            failed.is_empty(),
+            "failed to compile shader crates: {}",
+            failed.join(", ")
+        );
+
+        Ok(())
+    }
+
+    /// Compile the shader crate into a scratch directory in the cache, only to list the names of
+    /// its entry points.
+    ///
+    /// The names are the caller's output, so the build itself is quiet and only writes to
+    /// stderr.
+    pub fn entry_points(mut self) -> anyhow::Result<Vec<String>> {
+        self.install.is_quiet = true;
+        self.is_stdout_reserved = true;
+        self.message_format = MessageFormat::Human;
+        self.print_spv_paths = false;
+        let shader_crate = self.install.shader_crate().canonicalize()?;
+        self.output_dir = crate::cache_dir()?
+            .join("entry-points")
+            .join(crate::to_dirname(&shader_crate.to_string_lossy()));
+        self.shader_crate_glob = None;
+        self.manifest_relative_to_out_dir = None;
+        self.manifest_schema = ManifestSchema::Linkage;
+        self.build_shader_crate()?;
+
+        let manifest_path = self.output_dir.join("manifest.json");
+        let manifest: Vec<serde_json::Value> =
+            serde_json::from_reader(std::fs::File::open(&manifest_path).with_context(|| {
+                format!("could not open manifest '{}'", manifest_path.display())
+            })?)?;
+        let mut entry_points = manifest
+            .iter()
+            .map(|link| {
+                link.get("entry_point")
+                    .and_then(serde_json::Value::as_str)
+                    .map(ToOwned::to_owned)
+                    .context("manifest entry has no `entry_point`")
+            })
+            .collect::<anyhow::Result<Vec<String>>>()?;
+        entry_points.sort();
+        entry_points.dedup();
+        Ok(entry_points)
+    }
+
+    /// Ensure that there will be a target spec for `--shader-target` when compiling, as
+    /// otherwise `rustc` fails much later, with a less helpful "Could not find specification for
+    /// target".
+    fn ensure_target_is_available(&self) -> anyhow::Result<()> {
+        ensure_target_is_available(
+            &self.shader_target,
+            &crate::target_spec_path()?,
+            !self.install.no_target_spec_update,
+        )
+    }
+
+    /// Compile the single shader crate at `--shader-crate`.
+    fn build_shader_crate(&mut self) -> anyhow::Result<()> {
+        self.build_shader_crate_with(&mut Installs::new())
+    }
+
+    /// Resolve the shader crate's `rust-gpu` source and install its backend, unless `installs`
+    /// already has the backend for the same source and toolchain, from another shader crate.
+    fn install_backend(
+        &self,
+        installs: &mut Installs,
+    ) -> anyhow::Result<(SpirvCli, (std::path::PathBuf, std::path::PathBuf))> {
+        let spirv_cli = self.install.spirv_cli(self.install.shader_crate())?;
+        let checkout = spirv_cli.checkout_path()?;
+        let artifacts = if let Some(artifacts) = installs.get(&checkout) {
+            log::info!(
+                "reusing the backend installed in '{}' for {}",
+                checkout.display(),
+                self.install.shader_crate().display()
+            );
+            artifacts.clone()
+        } else {
+            let artifacts = self.install.run_with(spirv_cli.clone())?;
+            installs.insert(checkout, artifacts.clone());
+            artifacts
+        };
+        Ok((spirv_cli, artifacts))
+    }
+
+    /// Compile the single shader crate at `--shader-crate`, sharing the backends in `installs`
+    /// with the other shader crates of the build.
+    fn build_shader_crate_with(&mut self, installs: &mut Installs) -> anyhow::Result<()> {
+        let started = std::time::Instant::now();
+        self.ensure_target_is_available()?;
+        let (spirv_cli, (dylib_path, spirv_builder_cli_path)) = self.install_backend(installs)?;
 
         // Ensure the shader output dir exists
         log::debug!("ensuring output-dir '{}' exists", self.output_dir.display());
@@ -43,39 +571,27 @@ impl Build {
         self.output_dir = canonicalized;
 
         // Ensure the shader crate exists
-        self.install.shader_crate = self.install.shader_crate.canonicalize()?;
+        self.install.shader_crates = vec![self.install.shader_crate().canonicalize()?];
         anyhow::ensure!(
-            self.install.shader_crate.exists(),
+            self.install.shader_crate().exists(),
             "shader crate '{}' does not exist. (Current dir is '{}')",
-            self.install.shader_crate.display(),
+            self.install.shader_crate().display(),
             std::env::current_dir()?.display()
         );
 
-        let spirv_builder_args = spirv_builder_cli::Args {
-            dylib_path,
-            shader_crate: self.install.shader_crate.clone(),
-            shader_target: self.shader_target.clone(),
-            path_to_target_spec: target_spec_dir()?.join(format!("{}.json", self.shader_target)),
-            no_default_features: self.no_default_features,
-            features: self.features.clone(),
-            output_dir: self.output_dir.clone(),
-        };
-
-        let arg = serde_json::to_string_pretty(&spirv_builder_args)?;
-        log::info!("using spirv-builder-cli arg: {arg}");
-
-        crate::user_output!(
-            "Running `spirv-builder-cli` to compile shader at {}...\n",
-            self.install.shader_crate.display()
-        );
-
-        // Call spirv-builder-cli to compile the shaders.
-        let output = std::process::Command::new(spirv_builder_cli_path)
-            .arg(arg)
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .output()?;
-        anyhow::ensure!(output.status.success(), "build failed");
+        if !self.install.is_quiet {
+            crate::user_output!(
+                "Running `spirv-builder-cli` to compile shader at {}...\n",
+                self.install.shader_crate().display()
+            );
+        }
+        if self.auto_capabilities {
+            self.compile_with_auto_capabilities(&spirv_builder_cli_path, &dylib_path)?;
+        } else {
+            let (status, _) =
+                self.run_spirv_builder_cli(&spirv_builder_cli_path, &dylib_path, false)?;
+            anyhow::ensure!(status.success(), "build failed");
+        }
 
         let spirv_manifest = self.output_dir.join("spirv-manifest.json");
         if spirv_manifest.is_file() {
@@ -91,33 +607,45 @@ impl Build {
         let shaders: Vec<ShaderModule> =
             serde_json::from_reader(std::fs::File::open(&spirv_manifest)?)?;
 
-        let mut linkage: Vec<Linkage> = shaders
+        let manifest_root = self.manifest_root()?;
+        log::debug!(
+            "manifest paths will be relative to '{}'",
+            manifest_root.display()
+        );
+
+        let mut linkage: Vec<Linkage> = filter_shader_modules(shaders, &self.entry_point_filter)?
             .into_iter()
-            .map(
-                |ShaderModule {
-                     entry,
-                     path: filepath,
-                 }|
-                 -> anyhow::Result<Linkage> {
-                    use relative_path::PathExt as _;
-                    let path = self.output_dir.join(
-                        filepath
-                            .file_name()
-                            .context("Couldn't parse file name from shader module path")?,
-                    );
-                    std::fs::copy(&filepath, &path)?;
-                    let path_relative_to_shader_crate =
-                        path.relative_to(&self.install.shader_crate)?.to_path("");
-                    Ok(Linkage::new(entry, path_relative_to_shader_crate))
-                },
-            )
+            .map(|shader| self.copy_shader_module(shader, &manifest_root))
             .collect::<anyhow::Result<Vec<Linkage>>>()?;
 
+        let produced = spv_paths(&linkage, &manifest_root);
+        for path in foreign_modules(&self.output_dir, &self.spv_output_extension, &produced)? {
+            if !self.install.is_quiet {
+                crate::user_output!(
+                    "{} isn't from this build, so it may be a stale module of another shader crate. \
+                     Remove it, or use a separate `--output-dir` for each shader crate\n",
+                    path.display()
+                );
+            }
+        }
+
         // Write the shader manifest json file
         let manifest_path = self.output_dir.join("manifest.json");
+        ensure_unique_wgsl_entry_points(&linkage)?;
+
+        self.report_build(&spirv_cli, started, &linkage, &manifest_root)?;
+
+        // The paths are only rewritten now, as the reports above need to read the modules.
+        if self.path_separator != PathSeparator::Slash {
+            linkage = linkage
+                .into_iter()
+                .map(|link| link.with_path_separator(self.path_separator.as_str()))
+                .collect();
+        }
+
         // Sort the contents so the output is deterministic
         linkage.sort();
-        let json = serde_json::to_string_pretty(&linkage)?;
+        let json = manifest_json(self.manifest_schema, &linkage)?;
         let mut file = std::fs::File::create(&manifest_path).with_context(|| {
             format!(
                 "could not create shader manifest file '{}'",
@@ -133,6 +661,14 @@ impl Build {
 
         log::info!("wrote manifest to '{}'", manifest_path.display());
 
+        if let Some(symbols_path) = &self.emit_rust_symbols {
+            let path = self.output_dir.join(symbols_path);
+            std::fs::write(&path, rust_symbols(&linkage)?).with_context(|| {
+                format!("could not write Rust symbols file '{}'", path.display())
+            })?;
+            log::info!("wrote Rust symbols to '{}'", path.display());
+        }
+
         if spirv_manifest.is_file() {
             log::debug!(
                 "removing spirv-manifest.json file '{}'",
@@ -143,6 +679,664 @@ impl Build {
 
         Ok(())
     }
+
+    /// Run `spirv-builder-cli` to compile the shader crate, returning its exit status and,
+    /// when `is_stderr_captured`, its stderr, which is still printed as it's written.
+    fn run_spirv_builder_cli(
+        &self,
+        spirv_builder_cli_path: &std::path::Path,
+        dylib_path: &std::path::Path,
+        is_stderr_captured: bool,
+    ) -> anyhow::Result<(std::process::ExitStatus, String)> {
+        let spirv_builder_args = self.spirv_builder_args(dylib_path.to_path_buf())?;
+        let arg = serde_json::to_string_pretty(&spirv_builder_args)?;
+        log::info!("using spirv-builder-cli arg: {arg}");
+
+        // Call spirv-builder-cli to compile the shaders.
+        let mut command = std::process::Command::new(spirv_builder_cli_path);
+        if let Some(dir) = &self.install.toolchain_bin_dir {
+            // `spirv-builder` runs whichever `cargo` is first in `PATH`.
+            command.env("PATH", crate::spirv_cli::path_with_toolchain_bin_dir(dir)?);
+        }
+        for (key, value) in &self.install.cargo_config {
+            command.env(crate::install::cargo_config_env(key), value);
+        }
+        if let Some(opt_level) = &self.shader_opt_level {
+            command.env("CARGO_PROFILE_RELEASE_OPT_LEVEL", opt_level);
+        }
+        // With `--message-format json`, stdout is only for the JSON messages.
+        let stdout = if self.message_format == MessageFormat::Json || self.is_stdout_reserved {
+            std::io::stderr().into()
+        } else {
+            std::process::Stdio::inherit()
+        };
+        command.arg(arg).stdout(stdout);
+        if !is_stderr_captured {
+            let status = command.stderr(std::process::Stdio::inherit()).status()?;
+            return Ok((status, String::new()));
+        }
+
+        let mut child = command.stderr(std::process::Stdio::piped()).spawn()?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("could not capture the stderr of `spirv-builder-cli`")?;
+        let captured = crate::tee_stderr(stderr, std::io::stderr(), usize::MAX)?;
+        Ok((child.wait()?, captured))
+    }
+
+    /// Compile the shader crate, and whenever the compile errors say that it's missing
+    /// capabilities, add them to `--capabilities` and try again, a bounded number of times.
+    fn compile_with_auto_capabilities(
+        &mut self,
+        spirv_builder_cli_path: &std::path::Path,
+        dylib_path: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        for _ in 0..MAX_AUTO_CAPABILITIES_RETRIES {
+            let (status, stderr) =
+                self.run_spirv_builder_cli(spirv_builder_cli_path, dylib_path, true)?;
+            if status.success() {
+                return Ok(());
+            }
+            let missing = missing_capabilities(&stderr, &self.capabilities);
+            anyhow::ensure!(
+                !missing.is_empty(),
+                "build failed, and not because of missing capabilities"
+            );
+            let names: Vec<String> = missing
+                .iter()
+                .map(|capability| format!("{capability:?}"))
+                .collect();
+            if !self.install.is_quiet {
+                crate::user_output!(
+                    "Retrying with the missing capabilities {}, \
+                     consider adding them with `--capabilities`\n",
+                    names.join(", ")
+                );
+            }
+            self.capabilities.extend(missing);
+        }
+        let (status, _) = self.run_spirv_builder_cli(spirv_builder_cli_path, dylib_path, true)?;
+        anyhow::ensure!(
+            status.success(),
+            "build still failed after retrying with missing capabilities \
+             {MAX_AUTO_CAPABILITIES_RETRIES} times"
+        );
+        Ok(())
+    }
+
+    /// The arguments to `spirv-builder-cli` for compiling the shader crate.
+    fn spirv_builder_args(
+        &self,
+        dylib_path: std::path::PathBuf,
+    ) -> anyhow::Result<spirv_builder_cli::Args> {
+        let spec_dir = target_spec_dir()?;
+        let path_to_target_spec = spec_dir.join(format!("{}.json", self.shader_target));
+        Ok(spirv_builder_cli::Args {
+            dylib_path,
+            shader_crate: self.install.shader_crate().to_path_buf(),
+            shader_target: self.shader_target.clone(),
+            path_to_target_spec,
+            no_default_features: self.no_default_features,
+            features: self.features.clone(),
+            output_dir: self.output_dir.clone(),
+            capabilities: self
+                .capabilities
+                .iter()
+                .map(|capability| format!("{capability:?}"))
+                .collect(),
+            single_module: self.single_module,
+        })
+    }
+
+    /// Copy a compiled shader module into the output directory and create its manifest entry.
+    fn copy_shader_module(
+        &self,
+        ShaderModule {
+            entry,
+            path: filepath,
+        }: ShaderModule,
+        manifest_root: &std::path::Path,
+    ) -> anyhow::Result<Linkage> {
+        use relative_path::PathExt as _;
+        let path = self
+            .output_dir
+            .join(
+                filepath
+                    .file_name()
+                    .context("Couldn't parse file name from shader module path")?,
+            )
+            .with_extension(self.spv_output_extension.trim_start_matches('.'));
+        self.link_or_copy(&filepath, &path)?;
+        let path_relative_to_manifest_root = path.relative_to(manifest_root)?.to_path("");
+        let mut linkage = Linkage::new(entry, path_relative_to_manifest_root)
+            .with_wgsl_separator(self.entry_name_separator.as_deref().unwrap_or(""));
+        if self.manifest_target {
+            linkage.target = Some(self.shader_target.clone());
+        }
+        if self.reflect_capabilities || self.reflect_workgroup_sizes {
+            let module = SpirvModule::read(&path)?;
+            if self.reflect_capabilities {
+                linkage.capabilities = module
+                    .capabilities()
+                    .iter()
+                    .map(|capability| format!("{capability:?}"))
+                    .collect();
+            }
+            if self.reflect_workgroup_sizes {
+                linkage.workgroup_size = module.workgroup_size(&linkage.entry_point);
+            }
+        }
+        Ok(linkage)
+    }
+
+    /// Copy a compiled module, or hard-link it with `--link-artifacts`.
+    fn link_or_copy(&self, from: &std::path::Path, to: &std::path::Path) -> anyhow::Result<()> {
+        // Linking doesn't replace an existing file, and copying over a link left by an earlier
+        // `--link-artifacts` build would write through it into the target dir.
+        if to.is_file() {
+            std::fs::remove_file(to)
+                .with_context(|| format!("could not remove '{}'", to.display()))?;
+        }
+        if self.link_artifacts {
+            match std::fs::hard_link(from, to) {
+                Ok(()) => return Ok(()),
+                Err(error) => log::debug!(
+                    "could not hard-link '{}' to '{}', copying it instead: {error}",
+                    from.display(),
+                    to.display()
+                ),
+            }
+        }
+        std::fs::copy(from, to).with_context(|| {
+            format!("could not copy '{}' to '{}'", from.display(), to.display())
+        })?;
+        Ok(())
+    }
+
+    /// Print, or write, everything about the build that was asked for besides the manifest.
+    fn report_build(
+        &self,
+        spirv_cli: &SpirvCli,
+        started: std::time::Instant,
+        linkage: &[Linkage],
+        manifest_root: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        self.report_modules(linkage, manifest_root)?;
+        if self.print_spv_paths {
+            print_spv_paths(linkage, manifest_root);
+        }
+        if self.message_format == MessageFormat::Json {
+            for message in self.compiled_entry_points(linkage, manifest_root) {
+                message.emit()?;
+            }
+        }
+        if let Some(summary_path) = &self.summary_json {
+            let path = self.output_dir.join(summary_path);
+            let summary = self.summary(spirv_cli, started.elapsed(), linkage, manifest_root)?;
+            std::fs::write(&path, serde_json::to_string_pretty(&summary)?)
+                .with_context(|| format!("could not write build summary '{}'", path.display()))?;
+            log::info!("wrote build summary to '{}'", path.display());
+        }
+        Ok(())
+    }
+
+    /// Print the post-build reports about the compiled modules that were asked for.
+    fn report_modules(
+        &self,
+        linkage: &[Linkage],
+        manifest_root: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        if !self.dump_spirv_metadata && !self.report_unused_capabilities {
+            return Ok(());
+        }
+
+        let paths: std::collections::BTreeSet<std::path::PathBuf> = linkage
+            .iter()
+            .map(|link| manifest_root.join(&link.source_path))
+            .collect();
+        let mut used_capabilities = std::collections::BTreeSet::new();
+        for path in paths {
+            let module = SpirvModule::read(&path)?;
+            if self.dump_spirv_metadata && !self.install.is_quiet {
+                crate::user_output!("{}: {module}\n", path.display());
+            }
+            used_capabilities.extend(module.capabilities());
+        }
+
+        if self.report_unused_capabilities {
+            for capability in unused_capabilities(&self.capabilities, &used_capabilities) {
+                if !self.install.is_quiet {
+                    crate::user_output!(
+                        "capability `{capability:?}` is enabled with `--capabilities`, \
+                         but no compiled module declares it\n"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The `--message-format json` messages for the compiled entry points.
+    fn compiled_entry_points(
+        &self,
+        linkage: &[Linkage],
+        manifest_root: &std::path::Path,
+    ) -> Vec<Message> {
+        linkage
+            .iter()
+            .map(|link| Message::CompiledEntryPoint {
+                entry_point: link.entry_point.clone(),
+                shader_crate: self.install.shader_crate().to_path_buf(),
+                spv_path: manifest_root.join(&link.source_path),
+            })
+            .collect()
+    }
+
+    /// Summarise the build of the shader crate for `--summary-json`.
+    fn summary(
+        &self,
+        spirv_cli: &SpirvCli,
+        duration: core::time::Duration,
+        linkage: &[Linkage],
+        manifest_root: &std::path::Path,
+    ) -> anyhow::Result<BuildSummary> {
+        let paths: std::collections::BTreeSet<&str> = linkage
+            .iter()
+            .map(|link| link.source_path.as_str())
+            .collect();
+        let modules = paths
+            .into_iter()
+            .map(|path| {
+                let full_path = manifest_root.join(path);
+                let metadata = std::fs::metadata(&full_path)
+                    .with_context(|| format!("could not read module '{}'", full_path.display()))?;
+                Ok(ModuleSummary {
+                    path: path.to_owned(),
+                    size: metadata.len(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<ModuleSummary>>>()?;
+        let entry_points: std::collections::BTreeSet<&str> = linkage
+            .iter()
+            .map(|link| link.entry_point.as_str())
+            .collect();
+        Ok(BuildSummary {
+            shader_crate: self.install.shader_crate().to_path_buf(),
+            source: spirv_cli.source.to_string(),
+            channel: spirv_cli.channel.clone(),
+            target: self.shader_target.clone(),
+            duration_secs: duration.as_secs_f64(),
+            entry_point_count: entry_points.len(),
+            total_size: modules.iter().map(|module| module.size).sum(),
+            modules,
+        })
+    }
+
+    /// The directory that the manifest's shader paths are relative to.
+    fn manifest_root(&self) -> anyhow::Result<std::path::PathBuf> {
+        let root = match &self.manifest_relative_to_out_dir {
+            None => return Ok(self.install.shader_crate().to_path_buf()),
+            Some(Some(dir)) => dir.clone(),
+            Some(None) => std::env::var_os("OUT_DIR")
+                .map(std::path::PathBuf::from)
+                .context(
+                    "`--manifest-relative-to-out-dir` was given without a directory, \
+                     but `OUT_DIR` is not set (it is only set by cargo for build scripts)",
+                )?,
+        };
+        root.canonicalize().with_context(|| {
+            format!(
+                "could not find the manifest root directory '{}'",
+                root.display()
+            )
+        })
+    }
+}
+
+/// Ensure that there's a target spec for `target`, either one that `cargo gpu` writes to
+/// `spec_dir` when installing, if `is_spec_written`, or one already there.
+fn ensure_target_is_available(
+    target: &str,
+    spec_dir: &std::path::Path,
+    is_spec_written: bool,
+) -> anyhow::Result<()> {
+    let is_target_written =
+        is_spec_written && crate::install::target_names().any(|name| name == target);
+    if is_target_written || spec_dir.join(format!("{target}.json")).is_file() {
+        return Ok(());
+    }
+    let mut targets = Vec::new();
+    if is_spec_written {
+        targets.extend(crate::install::target_names().map(ToOwned::to_owned));
+    }
+    if spec_dir.is_dir() {
+        targets.extend(available_targets(spec_dir)?);
+    }
+    targets.sort();
+    targets.dedup();
+    let not_written = if is_spec_written {
+        ""
+    } else {
+        ", as `--no-target-spec-update` prevents writing them"
+    };
+    anyhow::bail!(
+        "there is no target spec for `--shader-target` '{target}' in '{}'{not_written}. \
+         The available targets are: {}",
+        spec_dir.display(),
+        targets.join(", ")
+    )
+}
+
+/// The targets that there are target specs for in `spec_dir`, sorted.
+fn available_targets(spec_dir: &std::path::Path) -> anyhow::Result<Vec<String>> {
+    let mut targets = std::fs::read_dir(spec_dir)
+        .with_context(|| format!("could not read target spec dir '{}'", spec_dir.display()))?
+        .map(|maybe_entry| Ok(maybe_entry?.path()))
+        .collect::<anyhow::Result<Vec<std::path::PathBuf>>>()?
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    targets.sort();
+    Ok(targets)
+}
+
+/// Keep only the compiled entry points named in `--entry-point`, or all of them when it isn't
+/// given. Names that aren't compiled entry points are an error, to catch typos.
+fn filter_shader_modules(
+    shaders: Vec<ShaderModule>,
+    filter: &[String],
+) -> anyhow::Result<Vec<ShaderModule>> {
+    if filter.is_empty() {
+        return Ok(shaders);
+    }
+    let unmatched: Vec<&str> = filter
+        .iter()
+        .filter(|name| !shaders.iter().any(|shader| shader.entry == **name))
+        .map(String::as_str)
+        .collect();
+    anyhow::ensure!(
+        unmatched.is_empty(),
+        "the shader crate has no entry points named {}, it has {}",
+        unmatched.join(", "),
+        shaders
+            .iter()
+            .map(|shader| shader.entry.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Ok(shaders
+        .into_iter()
+        .filter(|shader| filter.contains(&shader.entry))
+        .collect())
+}
+
+/// The paths of the compiled modules, without the duplicates of a multi-entry-point module.
+fn spv_paths(linkage: &[Linkage], manifest_root: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let paths: std::collections::BTreeSet<std::path::PathBuf> = linkage
+        .iter()
+        .map(|link| manifest_root.join(&link.source_path))
+        .collect();
+    paths.into_iter().collect()
+}
+
+/// The modules with the `--spv-output-extension` in `output_dir` that aren't the `produced`
+/// ones, eg left there by a build of another shader crate. The compiled modules are all written
+/// directly into `output_dir`, so they're told apart by their file names.
+fn foreign_modules(
+    output_dir: &std::path::Path,
+    extension: &str,
+    produced: &[std::path::PathBuf],
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let produced_names = produced
+        .iter()
+        .filter_map(|path| path.file_name())
+        .collect::<Vec<_>>();
+    let mut foreign = std::fs::read_dir(output_dir)
+        .with_context(|| format!("could not read output dir '{}'", output_dir.display()))?
+        .map(|maybe_entry| Ok(maybe_entry?.path()))
+        .collect::<anyhow::Result<Vec<std::path::PathBuf>>>()?
+        .into_iter()
+        .filter(|path| {
+            path.is_file()
+                && path.extension().is_some_and(|path_extension| {
+                    path_extension == extension.trim_start_matches('.')
+                })
+                && path
+                    .file_name()
+                    .is_some_and(|name| !produced_names.contains(&name))
+        })
+        .collect::<Vec<_>>();
+    foreign.sort();
+    Ok(foreign)
+}
+
+/// Print the paths of the compiled modules for `--print-spv-paths`.
+#[expect(
+    clippy::print_stdout,
+    reason = "The output is intended to be used in scripts, so no crab prefix"
+)]
+fn print_spv_paths(linkage: &[Linkage], manifest_root: &std::path::Path) {
+    for path in spv_paths(linkage, manifest_root) {
+        println!("{}", path.display());
+    }
+}
+
+/// The installed backends of a build's shader crates, (dylib, cli), by the checkout of their
+/// `rust-gpu` source and toolchain.
+type Installs =
+    std::collections::BTreeMap<std::path::PathBuf, (std::path::PathBuf, std::path::PathBuf)>;
+
+/// The most times that `--auto-capabilities` compiles a shader crate again, with the
+/// capabilities that the previous compile was missing.
+const MAX_AUTO_CAPABILITIES_RETRIES: usize = 3;
+
+/// Find the capabilities that compile errors say are missing, eg
+/// "error: `u8` without `OpCapability Int8`", skipping those that are already enabled.
+///
+/// This is a heuristic: the name of a capability on the same line as an error mentioning
+/// capabilities is taken to be missing.
+fn missing_capabilities(stderr: &str, enabled: &[spirv::Capability]) -> Vec<spirv::Capability> {
+    let mut missing = Vec::new();
+    let error_lines = stderr.lines().filter(|line| {
+        line.trim_start().starts_with("error") && line.to_lowercase().contains("capabilit")
+    });
+    for line in error_lines {
+        let words = line.split(|character: char| !character.is_ascii_alphanumeric());
+        for capability in words.filter_map(|word| word.parse::<spirv::Capability>().ok()) {
+            if !enabled.contains(&capability) && !missing.contains(&capability) {
+                missing.push(capability);
+            }
+        }
+    }
+    missing
+}
+
+/// The enabled capabilities that none of the compiled modules declare, without duplicates.
+fn unused_capabilities(
+    enabled: &[spirv::Capability],
+    used: &std::collections::BTreeSet<spirv::Capability>,
+) -> Vec<spirv::Capability> {
+    let mut unused = Vec::new();
+    for capability in enabled {
+        if !used.contains(capability) && !unused.contains(capability) {
+            unused.push(*capability);
+        }
+    }
+    unused
+}
+
+/// Mangling entry points' module paths can make distinct entry points clash, eg `a::main`
+/// and `amain` are both `amain`, so make sure that doesn't happen.
+fn ensure_unique_wgsl_entry_points(linkage: &[Linkage]) -> anyhow::Result<()> {
+    let mut entry_points = std::collections::BTreeMap::<&str, &str>::new();
+    for link in linkage {
+        match entry_points.insert(&link.wgsl_entry_point, &link.entry_point) {
+            Some(other) if other != link.entry_point => anyhow::bail!(
+                "entry points `{other}` and `{}` both have the wgsl entry point name `{}`, \
+                 try a different `--entry-name-separator`",
+                link.entry_point,
+                link.wgsl_entry_point
+            ),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// The Rust source of `--emit-rust-symbols`, with a `&str` constant of each entry point's
+/// module path.
+fn rust_symbols(linkage: &[Linkage]) -> anyhow::Result<String> {
+    let mut symbols = std::collections::BTreeMap::<String, &Linkage>::new();
+    for link in linkage {
+        let name = rust_const_name(&link.entry_point);
+        match symbols.insert(name.clone(), link) {
+            Some(other) if other.entry_point != link.entry_point => anyhow::bail!(
+                "entry points `{}` and `{}` would both be the Rust constant `{name}`",
+                other.entry_point,
+                link.entry_point
+            ),
+            _ => (),
+        }
+    }
+
+    let constants = symbols.into_iter().map(|(name, link)| {
+        format!(
+            "\n/// The module of the `{}` entry point.\npub const {name}: &str = {:?};\n",
+            link.entry_point, link.source_path
+        )
+    });
+    Ok(
+        core::iter::once("// Generated by `cargo gpu build`, do not edit.\n".to_owned())
+            .chain(constants)
+            .collect(),
+    )
+}
+
+/// Convert an entry point, eg `foo::main_vs`, into an upper snake case Rust identifier, eg
+/// `FOO_MAIN_VS`.
+fn rust_const_name(entry_point: &str) -> String {
+    let name: String = entry_point
+        .replace("::", "_")
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.starts_with(|character: char| character.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+/// Find the shader crates matching a glob pattern relative to the workspace root.
+fn expand_shader_crate_glob(
+    workspace_root: &std::path::Path,
+    pattern: &str,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let pattern_path = workspace_root.join(pattern);
+    let full_pattern = pattern_path
+        .to_str()
+        .with_context(|| format!("glob pattern '{}' is not UTF-8", pattern_path.display()))?;
+
+    let mut shader_crates = Vec::new();
+    for maybe_path in glob::glob(full_pattern)? {
+        let path = maybe_path?;
+        if is_shader_crate(&path) {
+            log::debug!("found shader crate '{}'", path.display());
+            shader_crates.push(path);
+        } else {
+            log::warn!(
+                "skipping '{}' as it is not a shader crate depending on `spirv-std`",
+                path.display()
+            );
+        }
+    }
+    Ok(shader_crates)
+}
+
+/// Whether the directory is a crate that has `spirv-std` amongst its dependencies, including
+/// target-specific dependencies and renamed dependencies.
+fn is_shader_crate(path: &std::path::Path) -> bool {
+    let Ok(contents) = std::fs::read_to_string(path.join("Cargo.toml")) else {
+        return false;
+    };
+    let Ok(toml) = toml::from_str::<toml::Table>(&contents) else {
+        return false;
+    };
+
+    let has_spirv_std = |dependencies: Option<&toml::Value>| {
+        dependencies
+            .and_then(toml::Value::as_table)
+            .is_some_and(|table| {
+                table.iter().any(|(name, dependency)| {
+                    let package = dependency.get("package").and_then(toml::Value::as_str);
+                    package.unwrap_or(name) == "spirv-std"
+                })
+            })
+    };
+
+    let mut targets = toml
+        .get("target")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(toml::Table::values);
+    has_spirv_std(toml.get("dependencies"))
+        || targets.any(|target| has_spirv_std(target.get("dependencies")))
+}
+
+/// Serialize the sorted shader linkage into the manifest's JSON, according to the schema.
+fn manifest_json(schema: ManifestSchema, linkage: &[Linkage]) -> anyhow::Result<String> {
+    Ok(match schema {
+        ManifestSchema::Linkage => serde_json::to_string_pretty(linkage)?,
+        ManifestSchema::ShaderModule => {
+            let shaders: Vec<ShaderModule> = linkage
+                .iter()
+                .map(|link| ShaderModule::new(&link.entry_point, &link.source_path))
+                .collect();
+            serde_json::to_string_pretty(&shaders)?
+        }
+        ManifestSchema::Map => {
+            let mut map = std::collections::BTreeMap::<&str, &str>::new();
+            for link in linkage {
+                match map.insert(&link.entry_point, &link.source_path) {
+                    Some(other) if other != link.source_path => anyhow::bail!(
+                        "entry point `{}` is in both '{other}' and '{}', \
+                         which `--manifest-schema map` can't represent",
+                        link.entry_point,
+                        link.source_path
+                    ),
+                    _ => (),
+                }
+            }
+            serde_json::to_string_pretty(&map)?
+        }
+        ManifestSchema::LinkageMap => {
+            let mut map = std::collections::BTreeMap::<&str, &Linkage>::new();
+            for link in linkage {
+                match map.insert(&link.entry_point, link) {
+                    Some(other) if other != link => anyhow::bail!(
+                        "entry point `{}` is in both '{}' and '{}', \
+                         which `--manifest-schema linkage-map` can't represent",
+                        link.entry_point,
+                        other.source_path,
+                        link.source_path
+                    ),
+                    _ => (),
+                }
+            }
+            serde_json::to_string_pretty(&map)?
+        }
+    })
 }
 
 #[cfg(test)]
@@ -168,9 +1362,10 @@ mod test {
         ];
         if let Cli {
             command: Command::Build(build),
+            ..
         } = Cli::parse_from(args)
         {
-            assert_eq!(shader_crate_path, build.install.shader_crate);
+            assert_eq!(shader_crate_path, build.install.shader_crate());
             assert_eq!(output_dir, build.output_dir);
 
             // TODO:
@@ -182,4 +1377,615 @@ mod test {
             panic!("was not a build command");
         }
     }
+
+    #[test_log::test]
+    fn manifest_relative_to_out_dir_from_params() {
+        let build_with = |extra_args: &[&str]| {
+            let args = ["target/debug/cargo-gpu", "build"]
+                .into_iter()
+                .chain(extra_args.iter().copied());
+            if let Cli {
+                command: Command::Build(build),
+                ..
+            } = Cli::parse_from(args)
+            {
+                build
+            } else {
+                panic!("was not a build command");
+            }
+        };
+
+        assert_eq!(None, build_with(&[]).manifest_relative_to_out_dir);
+        assert_eq!(
+            Some(None),
+            build_with(&["--manifest-relative-to-out-dir"]).manifest_relative_to_out_dir
+        );
+        assert_eq!(
+            Some(Some(std::path::PathBuf::from("some/dir"))),
+            build_with(&["--manifest-relative-to-out-dir", "some/dir"])
+                .manifest_relative_to_out_dir
+        );
+    }
+
+    #[test_log::test]
+    fn manifest_json_schemas() {
+        let linkage = [
+            Linkage::new("main_fs", "shaders/main_fs.spv"),
+            Linkage::new("foo::main_vs", "shaders/foo-main_vs.spv"),
+        ];
+
+        let as_linkage: serde_json::Value =
+            serde_json::from_str(&manifest_json(ManifestSchema::Linkage, &linkage).unwrap())
+                .unwrap();
+        assert_eq!(
+            serde_json::json!([
+                {
+                    "source_path": "shaders/main_fs.spv",
+                    "entry_point": "main_fs",
+                    "wgsl_entry_point": "main_fs",
+                },
+                {
+                    "source_path": "shaders/foo-main_vs.spv",
+                    "entry_point": "foo::main_vs",
+                    "wgsl_entry_point": "foomain_vs",
+                },
+            ]),
+            as_linkage
+        );
+
+        let as_shader_module: serde_json::Value =
+            serde_json::from_str(&manifest_json(ManifestSchema::ShaderModule, &linkage).unwrap())
+                .unwrap();
+        assert_eq!(
+            serde_json::json!([
+                { "entry": "main_fs", "path": "shaders/main_fs.spv" },
+                { "entry": "foo::main_vs", "path": "shaders/foo-main_vs.spv" },
+            ]),
+            as_shader_module
+        );
+
+        let workgroup_size: [u32; 3] = [8, 8, 1];
+        let mut with_workgroup_size = Linkage::new("main_cs", "shaders/main_cs.spv");
+        with_workgroup_size.workgroup_size = Some(workgroup_size);
+        let as_linkage_with_workgroup_size: serde_json::Value = serde_json::from_str(
+            &manifest_json(ManifestSchema::Linkage, &[with_workgroup_size]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            Some(&serde_json::Value::from(workgroup_size.to_vec())),
+            as_linkage_with_workgroup_size.pointer("/0/workgroup_size")
+        );
+
+        let mut with_target = Linkage::new("main_fs", "shaders/main_fs.spv");
+        with_target.target = Some("spirv-unknown-vulkan1.2".to_owned());
+        let as_linkage_with_target: serde_json::Value =
+            serde_json::from_str(&manifest_json(ManifestSchema::Linkage, &[with_target]).unwrap())
+                .unwrap();
+        assert_eq!(
+            serde_json::json!([{
+                "source_path": "shaders/main_fs.spv",
+                "entry_point": "main_fs",
+                "wgsl_entry_point": "main_fs",
+                "target": "spirv-unknown-vulkan1.2",
+            }]),
+            as_linkage_with_target
+        );
+
+        let as_map: serde_json::Value =
+            serde_json::from_str(&manifest_json(ManifestSchema::Map, &linkage).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "main_fs": "shaders/main_fs.spv",
+                "foo::main_vs": "shaders/foo-main_vs.spv",
+            }),
+            as_map
+        );
+
+        let as_linkage_map: serde_json::Value =
+            serde_json::from_str(&manifest_json(ManifestSchema::LinkageMap, &linkage).unwrap())
+                .unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "main_fs": {
+                    "source_path": "shaders/main_fs.spv",
+                    "entry_point": "main_fs",
+                    "wgsl_entry_point": "main_fs",
+                },
+                "foo::main_vs": {
+                    "source_path": "shaders/foo-main_vs.spv",
+                    "entry_point": "foo::main_vs",
+                    "wgsl_entry_point": "foomain_vs",
+                },
+            }),
+            as_linkage_map
+        );
+
+        let duplicated = [
+            Linkage::new("main_fs", "shaders/a.spv"),
+            Linkage::new("main_fs", "shaders/b.spv"),
+        ];
+        manifest_json(ManifestSchema::Map, &duplicated).unwrap_err();
+        manifest_json(ManifestSchema::LinkageMap, &duplicated).unwrap_err();
+    }
+
+    #[test_log::test]
+    fn manifest_path_separators() {
+        let source_path = |separator: PathSeparator| {
+            Linkage::new(
+                "main_fs",
+                std::path::Path::new("shaders").join("main_fs.spv"),
+            )
+            .with_path_separator(separator.as_str())
+            .source_path
+        };
+        assert_eq!("shaders/main_fs.spv", source_path(PathSeparator::Slash));
+        assert_eq!(
+            "shaders\\main_fs.spv",
+            source_path(PathSeparator::Backslash)
+        );
+        let native = if cfg!(windows) {
+            "shaders\\main_fs.spv"
+        } else {
+            "shaders/main_fs.spv"
+        };
+        assert_eq!(native, source_path(PathSeparator::Auto));
+    }
+
+    #[test_log::test]
+    fn build_summary_json() {
+        let summary = BuildSummary {
+            shader_crate: std::path::PathBuf::from("shaders"),
+            source: "v0.9.0".to_owned(),
+            channel: "nightly-2024-04-24".to_owned(),
+            target: "spirv-unknown-vulkan1.2".to_owned(),
+            duration_secs: 1.5,
+            entry_point_count: 2,
+            total_size: 1024,
+            modules: vec![ModuleSummary {
+                path: "shaders/module.spv".to_owned(),
+                size: 1024,
+            }],
+        };
+        let expected: serde_json::Value = serde_json::from_str(
+            r#"{
+                "shader_crate": "shaders",
+                "source": "v0.9.0",
+                "channel": "nightly-2024-04-24",
+                "target": "spirv-unknown-vulkan1.2",
+                "duration_secs": 1.5,
+                "entry_point_count": 2,
+                "total_size": 1024,
+                "modules": [{ "path": "shaders/module.spv", "size": 1024 }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(expected, serde_json::to_value(&summary).unwrap());
+    }
+
+    #[test_log::test]
+    fn json_messages() {
+        let shader_crate = std::path::PathBuf::from("shaders");
+        let mut build = Build::parse_from(["parent", "--message-format", "json"]);
+        assert_eq!(MessageFormat::Json, build.message_format);
+        build.install.shader_crates = vec![shader_crate.clone()];
+        let linkage = vec![Linkage::new("main_fs", "module.spv")];
+
+        let messages: Vec<String> = build
+            .compiled_entry_points(&linkage, &shader_crate)
+            .into_iter()
+            .chain([Message::BuildFinished { success: true }])
+            .map(|message| serde_json::to_string(&message).unwrap())
+            .collect();
+        let spv_path = shader_crate.join("module.spv");
+        assert_eq!(
+            vec![
+                format!(
+                    r#"{{"reason":"compiled-entry-point","entry_point":"main_fs","shader_crate":"shaders","spv_path":{}}}"#,
+                    serde_json::to_string(&spv_path).unwrap()
+                ),
+                r#"{"reason":"build-finished","success":true}"#.to_owned(),
+            ],
+            messages
+        );
+    }
+
+    #[test_log::test]
+    fn listing_available_targets() {
+        let dir = crate::test::fixture_dir("available-targets");
+        for filename in [
+            "spirv-unknown-vulkan1.2.json",
+            "spirv-unknown-opengl4.5.json",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.join(filename), "{}").unwrap();
+        }
+        assert_eq!(
+            vec!["spirv-unknown-opengl4.5", "spirv-unknown-vulkan1.2"],
+            available_targets(&dir).unwrap()
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn checking_target_availability() {
+        let dir = crate::test::fixture_dir("check-only-targets");
+        std::fs::write(dir.join("spirv-unknown-custom.json"), "{}").unwrap();
+
+        ensure_target_is_available("spirv-unknown-vulkan1.2", &dir, true).unwrap();
+        ensure_target_is_available("spirv-unknown-custom", &dir, true).unwrap();
+        let error = ensure_target_is_available("spirv-unknown-vulkan9", &dir, true)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("spirv-unknown-vulkan9"), "{error}");
+        assert!(error.contains("spirv-unknown-custom"), "{error}");
+        ensure_target_is_available("spirv-unknown-vulkan9", &dir.join("missing"), true)
+            .unwrap_err();
+
+        // With `--no-target-spec-update`, only the specs already there are available.
+        ensure_target_is_available("spirv-unknown-custom", &dir, false).unwrap();
+        let not_written = ensure_target_is_available("spirv-unknown-vulkan1.2", &dir, false)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            not_written.contains("--no-target-spec-update"),
+            "{not_written}"
+        );
+        assert!(
+            not_written.ends_with("The available targets are: spirv-unknown-custom"),
+            "{not_written}"
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn spv_output_extensions() {
+        let dir = crate::test::fixture_dir("spv-output-extensions");
+        let compiled = dir.join("compiled");
+        std::fs::create_dir_all(&compiled).unwrap();
+        let module = compiled.join("main_fs.spv");
+        std::fs::write(&module, [0; 4]).unwrap();
+
+        for (extension, filename) in [
+            ("spv", "main_fs.spv"),
+            (".spirv", "main_fs.spirv"),
+            ("bin", "main_fs.bin"),
+        ] {
+            let mut build = Build::parse_from(["parent", "--spv-output-extension", extension]);
+            build.output_dir = dir.join("out");
+            std::fs::create_dir_all(&build.output_dir).unwrap();
+            let linkage = build
+                .copy_shader_module(ShaderModule::new("main_fs", &module), &dir)
+                .unwrap();
+            assert_eq!(format!("out/{filename}"), linkage.source_path);
+            assert!(dir.join("out").join(filename).is_file());
+        }
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn linking_artifacts() {
+        let dir = crate::test::fixture_dir("linking-artifacts");
+        let module = dir.join("main_fs.spv");
+        std::fs::write(&module, [1; 4]).unwrap();
+        let output = dir.join("output.spv");
+        std::fs::write(&output, [0; 8]).unwrap();
+
+        let build = Build::parse_from(["parent", "--link-artifacts"]);
+        build.link_or_copy(&module, &output).unwrap();
+        assert_eq!(vec![1; 4], std::fs::read(&output).unwrap());
+        // Writing through one name is seen through the other, so it was linked.
+        std::fs::write(&module, [2; 4]).unwrap();
+        assert_eq!(vec![2; 4], std::fs::read(&output).unwrap());
+
+        // Copying over the link replaces it rather than writing through it.
+        Build::parse_from(["parent"])
+            .link_or_copy(&module, &output)
+            .unwrap();
+        std::fs::write(&module, [3; 4]).unwrap();
+        assert_eq!(vec![2; 4], std::fs::read(&output).unwrap());
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn shader_opt_levels_from_params() {
+        let build = Build::parse_from(["parent", "--shader-opt-level", "s"]);
+        assert_eq!(Some("s"), build.shader_opt_level.as_deref());
+        assert_eq!(None, Build::parse_from(["parent"]).shader_opt_level);
+        Build::try_parse_from(["parent", "--shader-opt-level", "4"]).unwrap_err();
+    }
+
+    #[test_log::test]
+    fn entry_name_separators_from_params() {
+        let build = Build::parse_from(["parent", "--entry-name-separator", "_"]);
+        assert_eq!(Some("_"), build.entry_name_separator.as_deref());
+        assert_eq!(None, Build::parse_from(["parent"]).entry_name_separator);
+        for separator in ["", "/", "\\", "::/"] {
+            Build::try_parse_from(["parent", "--entry-name-separator", separator]).unwrap_err();
+        }
+    }
+
+    #[test_log::test]
+    fn filtering_entry_points() {
+        let shaders = || {
+            vec![
+                ShaderModule::new("main_vs", "main_vs.spv"),
+                ShaderModule::new("main_fs", "main_fs.spv"),
+            ]
+        };
+        assert_eq!(shaders(), filter_shader_modules(shaders(), &[]).unwrap());
+        assert_eq!(
+            vec![ShaderModule::new("main_fs", "main_fs.spv")],
+            filter_shader_modules(shaders(), &["main_fs".to_owned()]).unwrap()
+        );
+        let error = filter_shader_modules(shaders(), &["main_fs".to_owned(), "main_cs".to_owned()])
+            .unwrap_err();
+        assert_eq!(
+            "the shader crate has no entry points named main_cs, it has main_vs, main_fs",
+            error.to_string()
+        );
+
+        let single_module = vec![
+            ShaderModule::new("main_vs", "module.spv"),
+            ShaderModule::new("main_fs", "module.spv"),
+        ];
+        assert_eq!(
+            vec![ShaderModule::new("main_vs", "module.spv")],
+            filter_shader_modules(single_module, &["main_vs".to_owned()]).unwrap()
+        );
+    }
+
+    #[test_log::test]
+    fn finding_foreign_modules() {
+        let dir = crate::test::fixture_dir("foreign-modules");
+        std::fs::create_dir_all(dir.join("nested.spv")).unwrap();
+        for filename in [
+            "main_fs.spv",
+            "other_crate_cs.spv",
+            "manifest.json",
+            "main_vs.bin",
+        ] {
+            std::fs::write(dir.join(filename), [0; 4]).unwrap();
+        }
+
+        assert_eq!(
+            vec![dir.join("other_crate_cs.spv")],
+            foreign_modules(&dir, "spv", &[dir.join("main_fs.spv")]).unwrap()
+        );
+        assert_eq!(
+            vec![dir.join("main_vs.bin")],
+            foreign_modules(&dir, ".bin", &[]).unwrap()
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn spv_paths_of_modules() {
+        let root = std::path::Path::new("/shaders");
+        let multi_module = [
+            Linkage::new("main_vs", "main_vs.spv"),
+            Linkage::new("main_fs", "main_fs.spv"),
+        ];
+        assert_eq!(
+            vec![root.join("main_fs.spv"), root.join("main_vs.spv")],
+            spv_paths(&multi_module, root)
+        );
+        let single_module = [
+            Linkage::new("main_vs", "module.spv"),
+            Linkage::new("main_fs", "module.spv"),
+        ];
+        assert_eq!(
+            vec![root.join("module.spv")],
+            spv_paths(&single_module, root)
+        );
+    }
+
+    #[test_log::test]
+    fn shader_crate_detection() {
+        let shader_crate_path = crate::test::shader_crate_template_path();
+        assert!(is_shader_crate(&shader_crate_path));
+        assert!(!is_shader_crate(&shader_crate_path.join("../cargo-gpu")));
+        assert!(!is_shader_crate(&shader_crate_path.join("src")));
+    }
+
+    #[test_log::test]
+    fn shader_crate_glob_expansion() {
+        let crates_dir = crate::test::shader_crate_template_path().join("..");
+        let shader_crates = expand_shader_crate_glob(&crates_dir, "*").unwrap();
+        assert_eq!(
+            vec![crates_dir.join("shader-crate-template")],
+            shader_crates
+        );
+    }
+
+    #[test_log::test]
+    fn single_module_manifest() {
+        let linkage = [
+            Linkage::new("main_fs", "shaders/module.spv"),
+            Linkage::new("main_vs", "shaders/module.spv"),
+        ];
+        ensure_unique_wgsl_entry_points(&linkage).unwrap();
+        let as_map: serde_json::Value =
+            serde_json::from_str(&manifest_json(ManifestSchema::Map, &linkage).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "main_fs": "shaders/module.spv",
+                "main_vs": "shaders/module.spv",
+            }),
+            as_map
+        );
+    }
+
+    #[test_log::test]
+    fn wgsl_entry_point_clashes() {
+        let clashing = [
+            Linkage::new("a::main", "a-main.spv"),
+            Linkage::new("amain", "amain.spv"),
+        ];
+        ensure_unique_wgsl_entry_points(&clashing).unwrap_err();
+
+        let separated = clashing.map(|link| link.with_wgsl_separator("_"));
+        ensure_unique_wgsl_entry_points(&separated).unwrap();
+        assert_eq!("a_main", separated[0].wgsl_entry_point);
+
+        let single_module = [
+            Linkage::new("main_fs", "module.spv"),
+            Linkage::new("main_fs", "module.spv"),
+        ];
+        ensure_unique_wgsl_entry_points(&single_module).unwrap();
+    }
+
+    #[test_log::test]
+    fn rust_symbols_of_entry_points() {
+        assert_eq!("MAIN_FS", rust_const_name("main_fs"));
+        assert_eq!("FOO_MAIN_VS", rust_const_name("foo::main_vs"));
+        assert_eq!("_2D_BLIT", rust_const_name("2d::blit"));
+
+        let linkage = [
+            Linkage::new("main_fs", "shaders/main_fs.spv"),
+            Linkage::new("foo::main_vs", "shaders/foo-main_vs.spv"),
+        ];
+        assert_eq!(
+            "// Generated by `cargo gpu build`, do not edit.\n\
+             \n\
+             /// The module of the `foo::main_vs` entry point.\n\
+             pub const FOO_MAIN_VS: &str = \"shaders/foo-main_vs.spv\";\n\
+             \n\
+             /// The module of the `main_fs` entry point.\n\
+             pub const MAIN_FS: &str = \"shaders/main_fs.spv\";\n",
+            rust_symbols(&linkage).unwrap()
+        );
+
+        let clashing = [
+            Linkage::new("foo::main", "foo-main.spv"),
+            Linkage::new("foo_main", "foo_main.spv"),
+        ];
+        rust_symbols(&clashing).unwrap_err();
+    }
+
+    #[test_log::test]
+    fn repeated_shader_crates() {
+        let dir = crate::test::fixture_dir("repeated-shader-crates");
+        let first = dir.join("first").join("shaders");
+        let second = dir.join("second").join("shaders");
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+
+        let args = [
+            "target/debug/cargo-gpu",
+            "build",
+            "--shader-crate",
+            &format!("{}", first.display()),
+            "--shader-crate",
+            &format!("{}", second.display()),
+        ];
+        if let Cli {
+            command: Command::Build(build),
+            ..
+        } = Cli::parse_from(args)
+        {
+            assert_eq!(vec![first.clone(), second], build.install.shader_crates);
+            assert_eq!(first, build.install.shader_crate());
+            assert_eq!(2, build.install.for_each_shader_crate().count());
+            // Both crates' directories are named `shaders`, so their outputs would clash.
+            let error = build
+                .build_shader_crates(&build.install.shader_crates)
+                .unwrap_err();
+            assert!(error.to_string().contains("would both be written to"));
+        } else {
+            panic!("was not a build command");
+        }
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn finding_missing_capabilities() {
+        let stderr = "   Compiling shader v0.1.0\n\
+                      error: `u8` without `OpCapability Int8`\n\
+                      note: Int16 is mentioned, but not in an error\n\
+                      error: `i64` without `OpCapability Int64`\n\
+                      error: `u8` without `OpCapability Int8`\n\
+                      error: Int16 is named in an unrelated error\n\
+                      error: missing capability: Float64\n";
+        assert_eq!(
+            vec![spirv::Capability::Int64, spirv::Capability::Float64],
+            missing_capabilities(stderr, &[spirv::Capability::Int8])
+        );
+        assert!(missing_capabilities("error: could not compile", &[]).is_empty());
+    }
+
+    #[test_log::test]
+    fn env_vars_from_params() {
+        let build_target = |extra_args: &[&str]| {
+            let args = ["target/debug/cargo-gpu", "build"]
+                .into_iter()
+                .chain(extra_args.iter().copied());
+            if let Cli {
+                command: Command::Build(build),
+                ..
+            } = Cli::parse_from(args)
+            {
+                build.shader_target
+            } else {
+                panic!("was not a build command");
+            }
+        };
+
+        crate::test::with_env(
+            "build::test::env_vars_from_params",
+            &[("CARGO_GPU_SHADER_TARGET", "spirv-unknown-spv1.3")],
+            || {
+                assert_eq!("spirv-unknown-spv1.3", build_target(&[]));
+                assert_eq!(
+                    "spirv-unknown-vulkan1.1",
+                    build_target(&["--shader-target", "spirv-unknown-vulkan1.1"])
+                );
+            },
+        );
+    }
+
+    #[test_log::test]
+    fn capabilities_from_params() {
+        let args = [
+            "target/debug/cargo-gpu",
+            "build",
+            "--capabilities",
+            "Int8",
+            "--capabilities",
+            "StorageImageWriteWithoutFormat",
+        ];
+        if let Cli {
+            command: Command::Build(build),
+            ..
+        } = Cli::parse_from(args)
+        {
+            assert_eq!(
+                vec![
+                    spirv::Capability::Int8,
+                    spirv::Capability::StorageImageWriteWithoutFormat
+                ],
+                build.capabilities
+            );
+        } else {
+            panic!("was not a build command");
+        }
+
+        assert!(
+            Cli::try_parse_from(["target/debug/cargo-gpu", "build", "--capabilities", "Nope"])
+                .is_err()
+        );
+    }
+
+    #[test_log::test]
+    fn reporting_unused_capabilities() {
+        let used = [spirv::Capability::Shader, spirv::Capability::Int8].into();
+        let enabled = [
+            spirv::Capability::Int8,
+            spirv::Capability::Int64,
+            spirv::Capability::Int64,
+        ];
+        assert_eq!(
+            vec![spirv::Capability::Int64],
+            unused_capabilities(&enabled, &used)
+        );
+    }
 }
@@ -0,0 +1,242 @@
+//! `cargo gpu doctor`, which checks that everything needed to build a shader crate is in place,
+//! and can fix some of what isn't.
+
+use anyhow::Context as _;
+
+use crate::{cache_dir, install::Install};
+
+/// `cargo gpu doctor`
+#[derive(clap::Parser, Debug)]
+pub struct Doctor {
+    /// The shader crate and toolchain settings to check against.
+    #[clap(flatten)]
+    pub install: Install,
+
+    /// Fix the problems that can be fixed safely: create the cache directory, write missing
+    /// target specs and install a missing toolchain or components.
+    ///
+    /// Installing asks for consent, unless `--auto-install-rust-toolchain` is also given.
+    #[clap(long)]
+    fix: bool,
+}
+
+impl Doctor {
+    /// Entrypoint
+    pub fn run(&self) -> anyhow::Result<()> {
+        let checks = [
+            self.check_cache_dir()?,
+            self.check_target_specs()?,
+            self.check_toolchain()?,
+        ];
+        let problems = checks.iter().filter(|is_ok| !**is_ok).count();
+        anyhow::ensure!(
+            problems == 0,
+            "found {problems} problem(s){}",
+            if self.fix {
+                ""
+            } else {
+                ", try `cargo gpu doctor --fix`"
+            }
+        );
+        crate::user_output!("Everything needed to build shaders is in place\n");
+        Ok(())
+    }
+
+    /// Report the outcome of a check.
+    fn report(status: &str, message: &str) {
+        crate::user_output!("[{status}] {message}\n");
+    }
+
+    /// Check that the cache directory exists.
+    fn check_cache_dir(&self) -> anyhow::Result<bool> {
+        let dir = cache_dir()?;
+        if dir.is_dir() {
+            Self::report("ok", &format!("cache directory '{}' exists", dir.display()));
+            return Ok(true);
+        }
+
+        if self.fix {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("could not create cache directory '{}'", dir.display()))?;
+            Self::report(
+                "fixed",
+                &format!("created cache directory '{}'", dir.display()),
+            );
+            return Ok(true);
+        }
+
+        Self::report(
+            "problem",
+            &format!("cache directory '{}' does not exist", dir.display()),
+        );
+        Ok(false)
+    }
+
+    /// Check that all of the target spec files are in the cache.
+    fn check_target_specs(&self) -> anyhow::Result<bool> {
+        let mut missing = crate::install::missing_target_specs()?;
+        if !missing.is_empty() && self.fix {
+            self.install.write_target_spec_files()?;
+            missing = crate::install::missing_target_specs()?;
+            if missing.is_empty() {
+                Self::report("fixed", "wrote the missing target specs");
+                return Ok(true);
+            }
+        }
+
+        if missing.is_empty() {
+            Self::report("ok", "all target specs are in the cache");
+            Ok(true)
+        } else {
+            Self::report(
+                "problem",
+                &format!("missing target specs: {}", missing.join(", ")),
+            );
+            Ok(false)
+        }
+    }
+
+    /// Check that the toolchain required by the shader crate, and its components, are installed.
+    fn check_toolchain(&self) -> anyhow::Result<bool> {
+        let is_rustup_managed = self.install.toolchain_bin_dir.is_none();
+        let spirv_cli = match self.install.spirv_cli(self.install.shader_crate()) {
+            Ok(spirv_cli) => spirv_cli,
+            Err(error) => {
+                Self::report(
+                    "problem",
+                    &format!(
+                        "could not resolve the `rust-gpu` version of shader crate '{}': {error}",
+                        self.install.shader_crate().display()
+                    ),
+                );
+                return Ok(false);
+            }
+        };
+        if is_rustup_managed && !spirv_cli.is_rustup_installed() {
+            Self::report(
+                "problem",
+                "`rustup` is not installed, install it from https://rustup.rs \
+                 or give the toolchain's location with `--toolchain-bin-dir`",
+            );
+            return Ok(false);
+        }
+
+        let is_installed = if is_rustup_managed {
+            spirv_cli.is_toolchain_installed()?
+                && spirv_cli.all_required_toolchain_components_installed()?
+        } else {
+            spirv_cli.ensure_toolchain_and_components_exist().is_ok()
+        };
+        if is_installed {
+            Self::report(
+                "ok",
+                &format!(
+                    "toolchain {} and its components are installed",
+                    spirv_cli.channel
+                ),
+            );
+            return Ok(true);
+        }
+
+        if self.fix && is_rustup_managed {
+            spirv_cli.ensure_toolchain_and_components_exist()?;
+            Self::report(
+                "fixed",
+                &format!(
+                    "installed toolchain {} and its components",
+                    spirv_cli.channel
+                ),
+            );
+            return Ok(true);
+        }
+
+        Self::report(
+            "problem",
+            &format!(
+                "toolchain {} or some of its components are not installed",
+                spirv_cli.channel
+            ),
+        );
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parse the arguments of `cargo gpu doctor`.
+    fn doctor_from_params(params: &[&str]) -> Doctor {
+        let args = ["target/debug/cargo-gpu", "doctor"].iter().chain(params);
+        if let crate::Cli {
+            command: crate::Command::Doctor(doctor),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(args)
+        {
+            doctor
+        } else {
+            panic!("was not a doctor command");
+        }
+    }
+
+    /// The names of the entries in `dir`, sorted.
+    fn entries(dir: &std::path::Path) -> Vec<String> {
+        let mut names = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test_log::test]
+    fn checking_cache_dir() {
+        let temp = crate::test::fixture_dir("doctor-cache-dir");
+        crate::test::with_env(
+            "doctor::test::checking_cache_dir",
+            &[("CARGO_GPU_CACHE_DIR", temp.to_str().unwrap())],
+            || {
+                let dir = cache_dir().unwrap();
+                assert!(dir.starts_with(&temp));
+                drop(std::fs::remove_dir_all(&dir));
+
+                assert!(!doctor_from_params(&[]).check_cache_dir().unwrap());
+                assert!(!dir.exists());
+
+                assert!(doctor_from_params(&["--fix"]).check_cache_dir().unwrap());
+                assert!(entries(&dir).is_empty());
+
+                assert!(doctor_from_params(&[]).check_cache_dir().unwrap());
+            },
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn checking_target_specs() {
+        let temp = crate::test::fixture_dir("doctor-target-specs");
+        crate::test::with_env(
+            "doctor::test::checking_target_specs",
+            &[("CARGO_GPU_CACHE_DIR", temp.to_str().unwrap())],
+            || {
+                let dir = cache_dir().unwrap();
+                drop(std::fs::remove_dir_all(&dir));
+                let specs = crate::target_spec_path().unwrap();
+                std::fs::create_dir_all(&specs).unwrap();
+                let kept = specs.join("spirv-unknown-vulkan1.2.json");
+                std::fs::write(&kept, "{}").unwrap();
+
+                assert!(!doctor_from_params(&[]).check_target_specs().unwrap());
+                assert_eq!(vec!["spirv-unknown-vulkan1.2.json"], entries(&specs));
+
+                assert!(doctor_from_params(&["--fix"]).check_target_specs().unwrap());
+                assert!(crate::install::missing_target_specs().unwrap().is_empty());
+                assert_eq!("{}", std::fs::read_to_string(&kept).unwrap());
+                assert_eq!(vec!["target-specs"], entries(&dir));
+
+                assert!(doctor_from_params(&[]).check_target_specs().unwrap());
+            },
+        );
+        crate::test::tests_teardown();
+    }
+}
@@ -3,7 +3,95 @@ use std::io::Write as _;
 
 use anyhow::Context as _;
 
-use crate::{cache_dir, spirv_cli::SpirvCli, spirv_source::SpirvSource, target_spec_dir};
+use crate::{
+    cache_dir,
+    spirv_cli::{SpirvCli, SpirvCliOptions},
+    spirv_source::SpirvSource,
+    target_spec_dir, target_spec_path,
+};
+
+/// Whether the `rust-gpu` source and toolchain of a shader crate could be resolved, and are
+/// installed, for `--spirv-source-only`.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResolutionStatus {
+    /// The source and toolchain are resolved, and their installation is in the cache.
+    Installed,
+    /// The source and toolchain are resolved, but they aren't installed.
+    NotInstalled,
+    /// The source or toolchain couldn't be resolved.
+    Unresolvable,
+}
+
+impl ResolutionStatus {
+    /// The exit code for the status, distinct from the codes of other errors.
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            Self::Installed => 0,
+            Self::NotInstalled => 10,
+            Self::Unresolvable => 11,
+        }
+    }
+}
+
+/// The `rust-gpu` source and toolchain of a shader crate, printed by `--spirv-source-only`.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    /// The shader crate that the source and toolchain were resolved for.
+    pub shader_crate: std::path::PathBuf,
+    /// Whether the source and toolchain were resolved, and are installed.
+    pub status: ResolutionStatus,
+    /// The resolved `rust-gpu` source.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// The resolved toolchain channel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// The directory that the source and toolchain are, or would be, installed in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_dir: Option<std::path::PathBuf>,
+    /// Why the source or toolchain couldn't be resolved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What installing the `rust-gpu` backend for a shader crate involves, as decided from its
+/// resolved source and toolchain and what's in the cache, before anything is installed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum InstallStep {
+    /// The installed backend can be used as it is.
+    UseInstalled,
+    /// The backend is copied from a `--prebuilt-backend` directory.
+    InstallPrebuilt(std::path::PathBuf),
+    /// The backend is built, for the given reason.
+    Build(&'static str),
+}
+
+impl core::fmt::Display for InstallStep {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UseInstalled => write!(f, "already installed, nothing to build"),
+            Self::InstallPrebuilt(dir) => {
+                write!(f, "would be installed from prebuilt '{}'", dir.display())
+            }
+            Self::Build(reason) => write!(f, "would be built, as {reason}"),
+        }
+    }
+}
+
+/// The installation of the `rust-gpu` backend for a shader crate.
+struct InstallPlan {
+    /// The resolved source and toolchain.
+    spirv_cli: SpirvCli,
+    /// The installation directory.
+    checkout: std::path::PathBuf,
+    /// The installed `rustc_codegen_spirv` dylib.
+    dylib_path: std::path::PathBuf,
+    /// The installed `spirv-builder-cli` executable.
+    cli_path: std::path::PathBuf,
+    /// What installing involves.
+    step: InstallStep,
+}
 
 /// These are the files needed to create the dedicated, per-shader `rust-gpu` builder create.
 const SPIRV_BUILDER_FILES: &[(&str, &str)] = &[
@@ -85,12 +173,231 @@ const TARGET_SPECS: &[(&str, &str)] = &[
     ),
 ];
 
+/// File in the checkout directory recording the `rustc` version that built the artifacts.
+const RUSTC_VERSION_FILENAME: &str = "rustc-version.txt";
+
+/// File in the checkout directory recording the SHA-256 of the installed `rustc_codegen_spirv`
+/// dylib, so that a corrupted or partially written one isn't trusted.
+const DYLIB_CHECKSUM_FILENAME: &str = "rustc_codegen_spirv.sha256";
+
+/// File in the checkout directory recording the SHA-256 of the `--backend-lockfile` that the
+/// installed artifacts were built with, if any.
+const BACKEND_LOCKFILE_CHECKSUM_FILENAME: &str = "backend-lockfile.sha256";
+
+/// File in the checkout directory recording the exact `rust-gpu` source of the artifacts, as the
+/// directory's name can't be turned back into the source.
+pub const SOURCE_FILENAME: &str = "source.json";
+
+/// The OS-dependent filename of the `rustc_codegen_spirv` dylib.
+pub fn dylib_filename() -> String {
+    format!(
+        "{}rustc_codegen_spirv{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    )
+}
+
+/// The SHA-256 of the file at `path`, in lowercase hex.
+fn sha256_hex(path: &std::path::Path) -> anyhow::Result<String> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("could not open '{}' to checksum it", path.display()))?;
+    let mut hasher = <sha2::Sha256 as sha2::Digest>::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("could not read '{}' to checksum it", path.display()))?;
+    Ok(sha2::Digest::finalize(hasher)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .concat())
+}
+
+/// Record the checksum of the installed dylib at `dylib_path` next to it.
+fn write_dylib_checksum(dylib_path: &std::path::Path) -> anyhow::Result<()> {
+    let checksum_path = dylib_path.with_file_name(DYLIB_CHECKSUM_FILENAME);
+    std::fs::write(&checksum_path, sha256_hex(dylib_path)?).with_context(|| {
+        format!(
+            "could not write dylib checksum file '{}'",
+            checksum_path.display()
+        )
+    })
+}
+
+/// Whether the installed dylib at `dylib_path` still matches its recorded checksum.
+/// Installations from before checksums were recorded don't have one, and are trusted.
+fn is_dylib_intact(dylib_path: &std::path::Path) -> anyhow::Result<bool> {
+    let checksum_path = dylib_path.with_file_name(DYLIB_CHECKSUM_FILENAME);
+    let Ok(recorded) = std::fs::read_to_string(&checksum_path) else {
+        log::debug!(
+            "no checksum of the installed dylib at '{}'",
+            checksum_path.display()
+        );
+        return Ok(true);
+    };
+    let is_intact = recorded.trim() == sha256_hex(dylib_path)?;
+    if !is_intact {
+        log::warn!(
+            "the installed dylib '{}' doesn't match its checksum in '{}', so it may be \
+             corrupted, rebuilding",
+            dylib_path.display(),
+            checksum_path.display()
+        );
+    }
+    Ok(is_intact)
+}
+
+/// The names of the targets that there are target specs for, eg "spirv-unknown-vulkan1.2".
+pub fn target_names() -> impl Iterator<Item = &'static str> {
+    TARGET_SPECS
+        .iter()
+        .map(|(filename, _)| filename.trim_end_matches(".json"))
+}
+
+/// The contents of the target spec for `target` that `cargo gpu` writes to the cache, if it has one.
+pub fn bundled_target_spec(target: &str) -> Option<&'static str> {
+    TARGET_SPECS
+        .iter()
+        .find(|(filename, _)| filename.trim_end_matches(".json") == target)
+        .map(|(_, contents)| *contents)
+}
+
+/// The target spec files that aren't in the cache yet.
+pub fn missing_target_specs() -> anyhow::Result<Vec<&'static str>> {
+    let dir = target_spec_path()?;
+    Ok(TARGET_SPECS
+        .iter()
+        .map(|(filename, _)| *filename)
+        .filter(|filename| !dir.join(filename).is_file())
+        .collect())
+}
+
+/// Parse a `--cargo-config` override of the form `KEY=VALUE`, where `KEY` is a dotted cargo
+/// config key like "net.retry".
+fn parse_cargo_config(config: &str) -> Result<(String, String), String> {
+    let (key, value) = config
+        .split_once('=')
+        .ok_or_else(|| format!("'{config}' is not of the form KEY=VALUE"))?;
+    let is_key_valid = !key.is_empty()
+        && key.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+        });
+    if !is_key_valid {
+        return Err(format!(
+            "'{key}' is not a cargo config key, eg \"net.retry\""
+        ));
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// The environment variable that overrides a cargo config key, eg `CARGO_NET_RETRY` for
+/// "net.retry".
+pub fn cargo_config_env(key: &str) -> String {
+    format!("CARGO_{}", key.to_uppercase().replace(['.', '-'], "_"))
+}
+
+/// The `spirv-builder` crate of the repo that a local `spirv-std` is in. The repo has already
+/// been found when it was cloned, so if it can't be found here we leave the path to cargo.
+fn spirv_builder_path(spirv_std_path: &str) -> String {
+    SpirvSource::find_local_repo_root(std::path::Path::new(spirv_std_path)).map_or_else(
+        || spirv_std_path.to_owned(),
+        |root| {
+            root.join("crates")
+                .join("spirv-builder")
+                .display()
+                .to_string()
+        },
+    )
+}
+
+/// Ensure that prebuilt artifacts were built by the `rustc` of the toolchain `channel`, as
+/// recorded in their `rustc-version.txt` or embedded in their dylib.
+fn ensure_same_rustc_version(prebuilt: &str, required: &str, channel: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        prebuilt.trim() == required.trim(),
+        "it was built with a `rustc` other than toolchain {channel}'s, so the dylib can't be \
+         loaded.\nBuilt with:\n{}\nNeeded:\n{}",
+        prebuilt.trim(),
+        required.trim()
+    );
+    Ok(())
+}
+
+/// The version of the `rustc` that built `dylib`, as embedded in its `.comment` section, eg
+/// "rustc 1.80.0-nightly (3105b3cb6 2024-04-24)" to compare with the first line of
+/// `rustc -vV`. Only ELF dylibs embed it, so it's `None` for others.
+fn embedded_rustc_version(dylib: &std::path::Path) -> anyhow::Result<Option<String>> {
+    const NEEDLE: &[u8] = b"rustc version ";
+    let contents = std::fs::read(dylib)
+        .with_context(|| format!("could not read dylib '{}'", dylib.display()))?;
+    let Some(start) = contents
+        .windows(NEEDLE.len())
+        .position(|window| window == NEEDLE)
+    else {
+        return Ok(None);
+    };
+    let version = contents
+        .get(start.saturating_add(NEEDLE.len())..)
+        .unwrap_or_default()
+        .split(|byte| *byte == b')')
+        .next()
+        .unwrap_or_default();
+    Ok(Some(format!("rustc {})", String::from_utf8_lossy(version))))
+}
+
+/// Quote a word for a POSIX shell, unless it's only made of characters that don't need it.
+pub fn shell_quote(word: &std::ffi::OsStr) -> String {
+    let text = word.to_string_lossy();
+    if !text.is_empty()
+        && text
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || "+-_./=:,@%".contains(char))
+    {
+        text.into_owned()
+    } else {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+/// A command as it could be typed into a POSIX shell, including the working directory and the
+/// environment variables that it sets, eg `cd '/cache/dir' && FOO=bar cargo +nightly build`.
+fn shell_command_line(command: &std::process::Command) -> String {
+    let directory = command
+        .get_current_dir()
+        .map(|dir| format!("cd {} && ", shell_quote(dir.as_os_str())))
+        .unwrap_or_default();
+    let envs = command
+        .get_envs()
+        .filter_map(|(key, maybe_value)| {
+            maybe_value.map(|value| format!("{}={} ", key.to_string_lossy(), shell_quote(value)))
+        })
+        .collect::<String>();
+    let words = core::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(shell_quote)
+        .collect::<Vec<_>>();
+    format!("{directory}{envs}{}", words.join(" "))
+}
+
 /// `cargo gpu install`
-#[derive(clap::Parser, Debug)]
+#[derive(clap::Parser, Debug, Clone)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each bool is an independent command line flag"
+)]
 pub struct Install {
     /// Directory containing the shader crate to compile.
-    #[clap(long, default_value = "./")]
-    pub shader_crate: std::path::PathBuf,
+    ///
+    /// This can be given more than once to install, or build, for several shader crates. Those
+    /// that share a `rust-gpu` source share one installation.
+    #[clap(
+        long = "shader-crate",
+        value_name = "SHADER_CRATE",
+        default_value = "./",
+        env = "CARGO_GPU_SHADER_CRATE"
+    )]
+    pub shader_crates: Vec<std::path::PathBuf>,
 
     #[expect(
         clippy::doc_markdown,
@@ -98,48 +405,297 @@ pub struct Install {
     )]
     /// Source of `spirv-builder` dependency
     /// Eg: "https://github.com/Rust-GPU/rust-gpu"
-    #[clap(long)]
+    #[clap(
+        long,
+        env = "CARGO_GPU_SPIRV_BUILDER_SOURCE",
+        group = "spirv_builder_git_source"
+    )]
     spirv_builder_source: Option<String>,
 
+    /// Short name of a well-known `rust-gpu` repo to use as `--spirv-builder-source`, eg
+    /// "rust-gpu" for the canonical repo. See `cargo gpu show source-aliases` for them all.
+    #[clap(
+        long,
+        value_name = "ALIAS",
+        value_parser = crate::spirv_source::parse_source_alias,
+        conflicts_with = "spirv_builder_source",
+        group = "spirv_builder_git_source"
+    )]
+    spirv_builder_source_alias: Option<String>,
+
     /// Version of `spirv-builder` dependency.
     /// * If `--spirv-builder-source` is not set, then this is assumed to be a crates.io semantic
     ///   version such as "0.9.0".
     /// * If `--spirv-builder-source` is set, then this is assumed to be a Git "commitsh", such
     ///   as a Git commit hash or a Git tag, therefore anything that `git checkout` can resolve.
-    #[clap(long, verbatim_doc_comment)]
+    #[clap(long, verbatim_doc_comment, env = "CARGO_GPU_SPIRV_BUILDER_VERSION")]
     spirv_builder_version: Option<String>,
 
+    /// File containing the Git "commitsh" of `--spirv-builder-source` to use, instead of
+    /// giving it with `--spirv-builder-version`.
+    ///
+    /// This lets a team pin `rust-gpu` in one file for all of its shader crates.
+    #[clap(
+        long,
+        value_name = "FILE",
+        requires = "spirv_builder_git_source",
+        conflicts_with = "spirv_builder_version"
+    )]
+    spirv_builder_rev_file: Option<std::path::PathBuf>,
+
+    /// Treat the shader crate as depending on this crates.io version of `spirv-std`, eg "0.9.0",
+    /// instead of resolving its `spirv-std` dependency.
+    ///
+    /// This picks the `rust-gpu` backend, and its toolchain, without looking at the shader crate.
+    #[clap(long, conflicts_with = "spirv_builder_git_source")]
+    spirv_std_version: Option<String>,
+
+    /// Resolve the shader crate's `spirv-std` dependency from the exact version or revision in
+    /// its `Cargo.lock`, rather than from `cargo tree`, which can differ when the lockfile is
+    /// stale.
+    ///
+    /// This makes sure that the `rust-gpu` backend matches what the shader crate links against.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "spirv_std_version",
+            "spirv_builder_git_source",
+            "spirv_builder_version",
+        ]
+    )]
+    spirv_builder_version_from_lock: bool,
+
     /// Rust toolchain channel to use to build `spirv-builder`.
     ///
     /// This must be compatible with the `spirv_builder` argument as defined in the `rust-gpu` repo.
-    #[clap(long)]
+    /// When given, the channel in the `rust-gpu` repo's `rust-toolchain.toml` isn't read at all,
+    /// which also works around a repo whose `rust-toolchain.toml` can't be parsed.
+    #[clap(long, env = "CARGO_GPU_RUST_TOOLCHAIN")]
     rust_toolchain: Option<String>,
 
-    /// Force `spirv-builder-cli` and `rustc_codegen_spirv` to be rebuilt.
+    /// Force `spirv-builder-cli` and `rustc_codegen_spirv` to be rebuilt, and the toolchain
+    /// channel of a crates.io release of `rust-gpu` to be looked up again rather than read from
+    /// the cache.
     #[clap(long)]
     force_spirv_cli_rebuild: bool,
 
     /// Assume "yes" to "Install Rust toolchain: [y/n]" prompt.
-    #[clap(long, action)]
+    #[clap(
+        long,
+        action,
+        env = "CARGO_GPU_AUTO_INSTALL_RUST_TOOLCHAIN",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
     auto_install_rust_toolchain: bool,
+
+    /// After a successful install, print the install directory and then the path to the
+    /// `rustc_codegen_spirv` dylib, each on its own line.
+    #[clap(long)]
+    print_install_dir: bool,
+
+    /// Report the resolved `rust-gpu` source and toolchain, whether the toolchain and the
+    /// backend are installed, and whether the backend would be built, without installing or
+    /// building anything.
+    ///
+    /// Resolving the toolchain may still clone the `rust-gpu` repo into the cache, unless it's a
+    /// crates.io release.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Print the shell command that builds `spirv-builder-cli` and `rustc_codegen_spirv`, with
+    /// its working directory and environment, before running it. The command is always
+    /// included in the error when the build fails, so that it can be reproduced by hand.
+    #[clap(long)]
+    print_backend_build_command: bool,
+
+    /// Build `spirv-builder-cli` and `rustc_codegen_spirv` with this `Cargo.lock`, passing
+    /// `--locked` to cargo, so that the backend's dependencies are the same on every install.
+    ///
+    /// Without it, cargo resolves the backend's dependencies afresh for each installation, so
+    /// they can differ between patch releases. A lockfile that is out of date is an error.
+    /// An installation that wasn't built with the same lockfile is rebuilt.
+    #[clap(long, value_name = "LOCKFILE")]
+    pub backend_lockfile: Option<std::path::PathBuf>,
+
+    /// Install a prebuilt `spirv-builder-cli` and `rustc_codegen_spirv` from this directory,
+    /// instead of building them, eg to share the backend that a team builds centrally.
+    ///
+    /// The directory is laid out like an installation in the cache, as printed by
+    /// `--print-install-dir`. Its `rustc-version.txt` must match the `rustc` of the toolchain
+    /// that the shader crate needs, as the dylib can only be loaded by that `rustc`. The
+    /// toolchain and target specs are still installed. Nothing is copied while the installed
+    /// `rustc-version.txt` and dylib are the same as the prebuilt ones.
+    #[clap(long, value_name = "DIR")]
+    pub prebuilt_backend: Option<std::path::PathBuf>,
+
+    /// Only install the Rust toolchain and components that the shader crate needs, without
+    /// building `spirv-builder-cli` and `rustc_codegen_spirv`.
+    ///
+    /// Useful when the `rust-gpu` backend is managed separately.
+    #[clap(long = "install-only-toolchain")]
+    pub only_toolchain: bool,
+
+    /// Only resolve the `rust-gpu` source and toolchain, print them as a line of JSON on stdout,
+    /// and exit with a code saying whether they're installed, without installing anything.
+    ///
+    /// The exit code is 0 when the source is resolved and installed, 10 when it's resolved but
+    /// not installed, and 11 when it can't be resolved. With several shader crates, the
+    /// highest code wins. Useful in CI to decide whether to run an expensive install step.
+    #[clap(long, conflicts_with = "only_toolchain")]
+    pub spirv_source_only: bool,
+
+    /// Print the `rustup` commands that would install the missing toolchain and components,
+    /// one per line, instead of installing them or anything else.
+    ///
+    /// For environments where `cargo gpu` mustn't run `rustup` itself, so that the commands can
+    /// be run separately. Nothing is printed when everything is already installed.
+    #[clap(long, conflicts_with_all = ["only_toolchain", "spirv_source_only"])]
+    pub print_toolchain_install_commands: bool,
+
+    /// Never access the network. Building `spirv-builder-cli` and the shader crate runs cargo
+    /// with `--offline`, and a missing `rust-gpu` checkout, toolchain or component is an error
+    /// instead of being downloaded.
+    ///
+    /// Useful for reproducible or air-gapped builds, with the cache and toolchains populated
+    /// beforehand.
+    #[clap(
+        long,
+        env = "CARGO_NET_OFFLINE",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    pub offline: bool,
+
+    /// Override a cargo config value, like `cargo --config KEY=VALUE`, eg "net.retry=5". Can be
+    /// given more than once.
+    ///
+    /// It's passed to the cargo that builds `spirv-builder-cli` as `--config`, and to the cargo
+    /// that builds the shader crate as a `CARGO_<KEY>` environment variable, as `spirv-builder`
+    /// doesn't forward cargo arguments. So the value must be a string, integer or boolean.
+    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_cargo_config)]
+    pub cargo_config: Vec<(String, String)>,
+
+    /// After a successful install, copy the target spec files into this directory and print
+    /// their paths, so that `rustc` can be invoked with them outside of `cargo gpu`.
+    #[clap(long, value_name = "DIR")]
+    copy_target_specs_to: Option<std::path::PathBuf>,
+
+    /// Cargo profile to build `spirv-builder-cli` and `rustc_codegen_spirv` with.
+    ///
+    /// Building with `dev` is faster and keeps debug info, which helps when debugging the
+    /// `rust-gpu` codegen backend itself. Previously installed artifacts are not rebuilt when
+    /// this changes, so combine it with `--force-spirv-cli-rebuild`.
+    #[clap(long, default_value = "release", value_name = "PROFILE")]
+    backend_profile: String,
+
+    /// Number of parallel jobs to build `spirv-builder-cli` and `rustc_codegen_spirv` with,
+    /// which is passed to `cargo build --jobs`. Fewer jobs need less memory, eg on small CI
+    /// machines. Defaults to cargo's default.
+    #[clap(long, value_name = "N")]
+    pub jobs: Option<u32>,
+
+    /// Never write the target spec files, not even when they are missing or when rebuilding.
+    ///
+    /// Use this to keep custom target specs in the cache's `target-specs` directory.
+    #[clap(long)]
+    pub no_target_spec_update: bool,
+
+    /// Directory containing the `cargo` and `rustc` binaries of the toolchain, for toolchains
+    /// that aren't managed by `rustup`, eg from a distro package or Nix.
+    ///
+    /// The toolchain must match `--rust-toolchain` and have the `rust-src`, `rustc-dev` and
+    /// `llvm-tools` components.
+    #[clap(long, value_name = "DIR")]
+    pub toolchain_bin_dir: Option<std::path::PathBuf>,
+
+    /// Whether the [`crate::user_output!`] messages of this install, or of the build that it's
+    /// part of, are silenced, eg as stdout is reserved for other output. Unlike `--quiet`, this
+    /// doesn't silence the rest of the process.
+    #[clap(skip)]
+    pub is_quiet: bool,
 }
 
 impl Install {
+    /// The shader crate to install for. When several are given this is the first, see
+    /// [`Self::for_each_shader_crate`].
+    pub fn shader_crate(&self) -> &std::path::Path {
+        self.shader_crates
+            .first()
+            .map_or_else(|| std::path::Path::new("./"), std::path::PathBuf::as_path)
+    }
+
+    /// A copy of these arguments for each of the shader crates, with only that shader crate.
+    pub fn for_each_shader_crate(&self) -> impl Iterator<Item = Self> + '_ {
+        self.shader_crates.iter().map(|shader_crate| Self {
+            shader_crates: vec![shader_crate.clone()],
+            ..self.clone()
+        })
+    }
+
     /// Returns a [`SpirvCLI`] instance, responsible for ensuring the right version of the `spirv-builder-cli` crate.
-    fn spirv_cli(&self, shader_crate_path: &std::path::PathBuf) -> anyhow::Result<SpirvCli> {
+    pub fn spirv_cli(&self, shader_crate_path: &std::path::Path) -> anyhow::Result<SpirvCli> {
+        let spirv_builder_version = match &self.spirv_builder_rev_file {
+            Some(path) => Some(Self::read_rev_file(path)?),
+            None => self.spirv_builder_version.clone(),
+        };
+        let spirv_std_source = if self.spirv_builder_version_from_lock {
+            Some(SpirvSource::get_spirv_std_dep_from_lockfile(
+                shader_crate_path,
+            )?)
+        } else {
+            self.spirv_std_version
+                .as_deref()
+                .map(SpirvSource::from_crates_io_version)
+                .transpose()?
+        };
         SpirvCli::new(
             shader_crate_path,
-            self.spirv_builder_source.clone(),
-            self.spirv_builder_version.clone(),
-            self.rust_toolchain.clone(),
-            self.auto_install_rust_toolchain,
+            SpirvCliOptions {
+                rust_gpu_source: self
+                    .spirv_builder_source
+                    .clone()
+                    .or_else(|| self.spirv_builder_source_alias.clone()),
+                rust_gpu_version: spirv_builder_version,
+                rust_gpu_channel: self.rust_toolchain.clone(),
+                is_toolchain_install_consent: self.auto_install_rust_toolchain,
+                toolchain_bin_dir: self.toolchain_bin_dir.clone(),
+                spirv_std_source,
+                is_channel_requeried: self.force_spirv_cli_rebuild,
+            },
         )
     }
 
+    /// Read the single Git revision in a `--spirv-builder-rev-file`.
+    fn read_rev_file(path: &std::path::Path) -> anyhow::Result<String> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read rev file '{}'", path.display()))?;
+        let mut words = contents.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some(rev), None) => Ok(rev.to_owned()),
+            _ => anyhow::bail!(
+                "rev file '{}' must contain exactly one Git revision, found '{}'",
+                path.display(),
+                contents.trim()
+            ),
+        }
+    }
+
+    /// Install the Rust toolchain and components needed by the shader crate, and nothing else.
+    pub fn install_toolchain(&self) -> anyhow::Result<()> {
+        let spirv_cli = self.spirv_cli(self.shader_crate())?;
+        spirv_cli.ensure_toolchain_and_components_exist()?;
+        log::info!(
+            "toolchain {} and its components are installed",
+            spirv_cli.channel
+        );
+        Ok(())
+    }
+
     /// Create the `spirv-builder-cli` crate.
-    fn write_source_files(&self) -> anyhow::Result<()> {
-        let spirv_cli = self.spirv_cli(&self.shader_crate)?;
-        let checkout = spirv_cli.cached_checkout_path()?;
+    fn write_source_files(
+        &self,
+        spirv_cli: &SpirvCli,
+        checkout: &std::path::Path,
+    ) -> anyhow::Result<()> {
         std::fs::create_dir_all(checkout.join("src"))?;
         for (filename, contents) in SPIRV_BUILDER_FILES {
             log::debug!("writing {filename}");
@@ -151,9 +707,52 @@ impl Install {
             }
             file.write_all(replaced_contents.as_bytes())?;
         }
+        self.copy_backend_lockfile(checkout)
+    }
+
+    /// Copy the `--backend-lockfile`, if any, into the checkout directory.
+    fn copy_backend_lockfile(&self, checkout: &std::path::Path) -> anyhow::Result<()> {
+        if let Some(lockfile) = &self.backend_lockfile {
+            log::debug!("copying backend lockfile '{}'", lockfile.display());
+            std::fs::copy(lockfile, checkout.join("Cargo.lock")).with_context(|| {
+                format!("could not copy backend lockfile '{}'", lockfile.display())
+            })?;
+        }
         Ok(())
     }
 
+    /// Record the checksum of the `--backend-lockfile` that the artifacts in the checkout
+    /// directory were just built with, or that they weren't built with one.
+    fn write_backend_lockfile_checksum(&self, checkout: &std::path::Path) -> anyhow::Result<()> {
+        let checksum_path = checkout.join(BACKEND_LOCKFILE_CHECKSUM_FILENAME);
+        let Some(lockfile) = &self.backend_lockfile else {
+            if checksum_path.is_file() {
+                std::fs::remove_file(&checksum_path)?;
+            }
+            return Ok(());
+        };
+        std::fs::write(&checksum_path, sha256_hex(lockfile)?).with_context(|| {
+            format!(
+                "could not write backend lockfile checksum file '{}'",
+                checksum_path.display()
+            )
+        })
+    }
+
+    /// Whether the installed artifacts weren't built with the `--backend-lockfile`, if one is
+    /// given.
+    fn is_backend_lockfile_changed(&self, checkout: &std::path::Path) -> anyhow::Result<bool> {
+        let Some(lockfile) = &self.backend_lockfile else {
+            return Ok(false);
+        };
+        let Ok(recorded) =
+            std::fs::read_to_string(checkout.join(BACKEND_LOCKFILE_CHECKSUM_FILENAME))
+        else {
+            return Ok(true);
+        };
+        Ok(recorded.trim() != sha256_hex(lockfile)?)
+    }
+
     /// Update  the `Cargo.toml` file in the `spirv-builder-cli` crate so that it contains
     /// the correct version of `spirv-builder-cli`.
     fn update_cargo_toml(contents: &str, spirv_source: &SpirvSource) -> String {
@@ -162,15 +761,23 @@ impl Install {
                 let replaced_line = match spirv_source {
                     SpirvSource::CratesIO(_) => String::new(),
                     SpirvSource::Git { url, .. } => format!("git = \"{url}\""),
-                    SpirvSource::Path((path, _)) => format!("path = \"{path}\""),
+                    // Paths can contain characters that need escaping, like Windows' `\`.
+                    SpirvSource::Path((path, _)) => {
+                        format!("path = {}", toml::Value::from(spirv_builder_path(path)))
+                    }
                 };
                 return format!("{replaced_line}\n");
             }
 
             if line.contains("${AUTO-REPLACE-VERSION}") {
                 let replaced_line = match spirv_source {
-                    SpirvSource::CratesIO(version) | SpirvSource::Path((_, version)) => {
-                        format!("version = \"{}\"", version.replace('v', ""))
+                    // Pin the exact crates.io release so that the build only needs the
+                    // package from cargo's registry cache, never a checkout of the repo.
+                    SpirvSource::CratesIO(version) => {
+                        format!("version = \"={}\"", version.trim_start_matches('v'))
+                    }
+                    SpirvSource::Path((_, version)) => {
+                        format!("version = \"{}\"", version.trim_start_matches('v'))
                     }
                     SpirvSource::Git { rev, .. } => format!("rev = \"{rev}\""),
                 };
@@ -184,7 +791,14 @@ impl Install {
     }
 
     /// Add the target spec files to the crate.
-    fn write_target_spec_files(&self) -> anyhow::Result<()> {
+    pub fn write_target_spec_files(&self) -> anyhow::Result<()> {
+        if self.no_target_spec_update {
+            log::info!(
+                "not updating the target specs in '{}'",
+                target_spec_dir()?.display()
+            );
+            return Ok(());
+        }
         for (filename, contents) in TARGET_SPECS {
             let path = target_spec_dir()?.join(filename);
             if !path.is_file() || self.force_spirv_cli_rebuild {
@@ -195,8 +809,56 @@ impl Install {
         Ok(())
     }
 
+    /// Resolve the `rust-gpu` source and toolchain of the shader crate, and check whether they're
+    /// installed, for `--spirv-source-only`. Nothing is installed.
+    pub fn resolve(&self) -> Resolution {
+        let shader_crate = self.shader_crate().to_path_buf();
+        let resolved = self.spirv_cli(&shader_crate).and_then(|spirv_cli| {
+            let install_dir = spirv_cli.checkout_path()?;
+            Ok((spirv_cli, install_dir))
+        });
+        match resolved {
+            Ok((spirv_cli, install_dir)) => {
+                let is_installed = install_dir.join(dylib_filename()).is_file()
+                    && install_dir.join("spirv-builder-cli").is_file();
+                Resolution {
+                    shader_crate,
+                    status: if is_installed {
+                        ResolutionStatus::Installed
+                    } else {
+                        ResolutionStatus::NotInstalled
+                    },
+                    source: Some(spirv_cli.source.to_string()),
+                    channel: Some(spirv_cli.channel),
+                    install_dir: Some(install_dir),
+                    error: None,
+                }
+            }
+            Err(error) => {
+                log::error!("{error:?}");
+                Resolution {
+                    shader_crate,
+                    status: ResolutionStatus::Unresolvable,
+                    source: None,
+                    channel: None,
+                    install_dir: None,
+                    error: Some(format!("{error:#}")),
+                }
+            }
+        }
+    }
+
     /// Install the binary pair and return the paths, (dylib, cli).
     pub fn run(&self) -> anyhow::Result<(std::path::PathBuf, std::path::PathBuf)> {
+        self.run_with(self.spirv_cli(self.shader_crate())?)
+    }
+
+    /// Install the binary pair for the already resolved `spirv_cli` and return the paths,
+    /// (dylib, cli).
+    pub fn run_with(
+        &self,
+        spirv_cli: SpirvCli,
+    ) -> anyhow::Result<(std::path::PathBuf, std::path::PathBuf)> {
         // Ensure the cache dir exists
         let cache_dir = cache_dir()?;
         log::info!("cache directory is '{}'", cache_dir.display());
@@ -204,89 +866,447 @@ impl Install {
             format!("could not create cache directory '{}'", cache_dir.display())
         })?;
 
-        let spirv_version = self.spirv_cli(&self.shader_crate)?;
-        spirv_version.ensure_toolchain_and_components_exist()?;
+        spirv_cli.ensure_toolchain_and_components_exist()?;
+        let InstallPlan {
+            spirv_cli: spirv_version,
+            checkout,
+            dylib_path: dest_dylib_path,
+            cli_path: dest_cli_path,
+            step,
+        } = self.plan(spirv_cli, true)?;
+        let rustc_version_path = checkout.join(RUSTC_VERSION_FILENAME);
 
-        let checkout = spirv_version.cached_checkout_path()?;
-        let release = checkout.join("target").join("release");
+        match step {
+            InstallStep::UseInstalled => {
+                log::info!("...and so we are aborting the install step.");
+            }
+            InstallStep::InstallPrebuilt(prebuilt) => {
+                self.write_target_spec_files()?;
+                self.install_prebuilt_backend(&prebuilt, &spirv_version, &checkout)?;
+            }
+            InstallStep::Build(reason) => {
+                log::info!("building the backend, as {reason}");
+                log::debug!(
+                    "writing spirv-builder-cli source files into '{}'",
+                    checkout.display()
+                );
+                self.write_source_files(&spirv_version, &checkout)?;
+                self.write_target_spec_files()?;
 
-        let dylib_filename = format!(
-            "{}rustc_codegen_spirv{}",
-            std::env::consts::DLL_PREFIX,
-            std::env::consts::DLL_SUFFIX
-        );
-        let dylib_path = release.join(&dylib_filename);
-        let dest_dylib_path = checkout.join(&dylib_filename);
-        let dest_cli_path = checkout.join("spirv-builder-cli");
-        if dest_dylib_path.is_file() && dest_cli_path.is_file() {
+                if !self.is_quiet {
+                    crate::user_output!(
+                        "Compiling shader-specific `spirv-builder-cli` for {}\n",
+                        self.shader_crate().display()
+                    );
+                }
+
+                self.build_backend(&spirv_version, &checkout)?;
+
+                let release = checkout
+                    .join("target")
+                    .join(Self::profile_target_dir(&self.backend_profile));
+                Self::move_built_artifacts(&release, &dest_dylib_path, &dest_cli_path)?;
+                self.write_backend_lockfile_checksum(&checkout)?;
+
+                std::fs::write(&rustc_version_path, spirv_version.rustc_version()?).with_context(
+                    || {
+                        format!(
+                            "could not write rustc version file '{}'",
+                            rustc_version_path.display()
+                        )
+                    },
+                )?;
+            }
+        }
+
+        let source_path = checkout.join(SOURCE_FILENAME);
+        if !source_path.is_file() {
+            std::fs::write(&source_path, serde_json::to_string(&spirv_version.source)?)
+                .with_context(|| {
+                    format!("could not write source file '{}'", source_path.display())
+                })?;
+        }
+
+        if self.print_install_dir {
+            #[expect(
+                clippy::print_stdout,
+                reason = "The output is intended to be captured by scripts, so no crab prefix"
+            )]
+            {
+                println!("{}", checkout.display());
+                println!("{}", dest_dylib_path.display());
+            }
+        }
+
+        if let Some(dir) = &self.copy_target_specs_to {
+            self.copy_target_spec_files(dir)?;
+        }
+
+        Ok((dest_dylib_path, dest_cli_path))
+    }
+
+    /// Decide what installing the backend for the resolved `spirv_cli` involves, without
+    /// changing anything. Whether a local `rust-gpu` checkout's toolchain has changed is only
+    /// checked when `can_run_rustc`, as it needs the toolchain to be installed.
+    fn plan(&self, spirv_cli: SpirvCli, can_run_rustc: bool) -> anyhow::Result<InstallPlan> {
+        let checkout = spirv_cli.checkout_path()?;
+        let dylib_path = checkout.join(dylib_filename());
+        let cli_path = checkout.join("spirv-builder-cli");
+        let is_installed = dylib_path.is_file() && cli_path.is_file();
+        if is_installed {
             log::info!(
                 "cargo-gpu artifacts are already installed in '{}'",
                 checkout.display()
             );
         }
 
-        if dest_dylib_path.is_file() && dest_cli_path.is_file() && !self.force_spirv_cli_rebuild {
-            log::info!("...and so we are aborting the install step.");
+        let step = if let Some(prebuilt) = &self.prebuilt_backend {
+            if !self.force_spirv_cli_rebuild && Self::is_prebuilt_installed(prebuilt, &checkout)? {
+                InstallStep::UseInstalled
+            } else {
+                InstallStep::InstallPrebuilt(prebuilt.clone())
+            }
+        } else if !is_installed {
+            InstallStep::Build("it isn't installed yet")
+        } else if self.force_spirv_cli_rebuild {
+            InstallStep::Build("`--force-spirv-cli-rebuild` was given")
+        } else if can_run_rustc
+            // A local `rust-gpu` checkout can be built with a toolchain that has since changed,
+            // in which case the installed artifacts can no longer be trusted.
+            && matches!(spirv_cli.source, SpirvSource::Path(_))
+            && Self::is_rustc_changed(&spirv_cli, &checkout.join(RUSTC_VERSION_FILENAME))?
+        {
+            InstallStep::Build("the toolchain's `rustc` has changed since it was built")
+        } else if self.is_backend_lockfile_changed(&checkout)? {
+            InstallStep::Build("it wasn't built with `--backend-lockfile`")
+        } else if !is_dylib_intact(&dylib_path)? {
+            InstallStep::Build("the installed dylib doesn't match its checksum")
+        } else {
+            InstallStep::UseInstalled
+        };
+
+        Ok(InstallPlan {
+            spirv_cli,
+            checkout,
+            dylib_path,
+            cli_path,
+            step,
+        })
+    }
+
+    /// For `--dry-run`, print what installing for the shader crate would do.
+    #[expect(
+        clippy::print_stdout,
+        reason = "The report is intended to be read in CI logs, so no crab prefix"
+    )]
+    pub fn dry_run(&self) -> anyhow::Result<()> {
+        let spirv_cli = self.spirv_cli(self.shader_crate())?;
+        let toolchain_install_commands = spirv_cli.toolchain_install_commands()?;
+        let plan = self.plan(spirv_cli, toolchain_install_commands.is_empty())?;
+
+        println!("shader crate: {}", self.shader_crate().display());
+        println!("source: {}", plan.spirv_cli.source);
+        if toolchain_install_commands.is_empty() {
+            println!("toolchain: {} (installed)", plan.spirv_cli.channel);
+        } else {
+            println!(
+                "toolchain: {} (would be installed with `{}`)",
+                plan.spirv_cli.channel,
+                toolchain_install_commands.join(" && ")
+            );
+        }
+        println!("install dir: {}", plan.checkout.display());
+        println!("backend: {}\n", plan.step);
+        Ok(())
+    }
+
+    /// Move the freshly built dylib and `spirv-builder-cli` out of the `release` target dir, to
+    /// their installed paths.
+    fn move_built_artifacts(
+        release: &std::path::Path,
+        dest_dylib_path: &std::path::Path,
+        dest_cli_path: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        let dylib_path = release.join(dylib_filename());
+        if dylib_path.is_file() {
+            log::info!("successfully built {}", dylib_path.display());
+            std::fs::rename(&dylib_path, dest_dylib_path)?;
+            write_dylib_checksum(dest_dylib_path)?;
+        } else {
+            log::error!("could not find {}", dylib_path.display());
+            anyhow::bail!("spirv-builder-cli build failed");
+        }
+
+        let cli_path = if cfg!(target_os = "windows") {
+            release.join("spirv-builder-cli").with_extension("exe")
+        } else {
+            release.join("spirv-builder-cli")
+        };
+        if cli_path.is_file() {
+            log::info!("successfully built {}", cli_path.display());
+            std::fs::rename(&cli_path, dest_cli_path)?;
+        } else {
+            log::error!("could not find {}", cli_path.display());
+            log::debug!("contents of '{}':", release.display());
+            for maybe_entry in std::fs::read_dir(release)? {
+                let entry = maybe_entry?;
+                log::debug!("{}", entry.file_name().to_string_lossy());
+            }
+            anyhow::bail!("spirv-builder-cli build failed");
+        }
+        Ok(())
+    }
+
+    /// Copy the prebuilt artifacts in `prebuilt` into the checkout directory, once they're
+    /// known to have been built by the shader crate's `rustc`.
+    ///
+    /// That's the version of `rustc` embedded in the dylib, which has to match its
+    /// `rustc-version.txt` too. A dylib that doesn't embed it, as only ELF ones do, is trusted to
+    /// match its `rustc-version.txt`.
+    fn install_prebuilt_backend(
+        &self,
+        prebuilt: &std::path::Path,
+        spirv_cli: &SpirvCli,
+        checkout: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        let rustc_version_path = prebuilt.join(RUSTC_VERSION_FILENAME);
+        let prebuilt_rustc_version =
+            std::fs::read_to_string(&rustc_version_path).with_context(|| {
+                format!(
+                    "could not read '{}', which records the `rustc` that built the prebuilt backend",
+                    rustc_version_path.display()
+                )
+            })?;
+        let required_rustc_version = spirv_cli.rustc_version()?;
+        ensure_same_rustc_version(
+            &prebuilt_rustc_version,
+            &required_rustc_version,
+            &spirv_cli.channel,
+        )
+        .with_context(|| {
+            format!(
+                "can't install the prebuilt backend in '{}'",
+                prebuilt.display()
+            )
+        })?;
+        let dylib = prebuilt.join(dylib_filename());
+        if let Some(embedded) = embedded_rustc_version(&dylib)? {
+            ensure_same_rustc_version(
+                &embedded,
+                required_rustc_version.lines().next().unwrap_or_default(),
+                &spirv_cli.channel,
+            )
+            .with_context(|| {
+                format!(
+                    "can't install the prebuilt backend in '{}', as its dylib doesn't match its \
+                     '{RUSTC_VERSION_FILENAME}'",
+                    prebuilt.display()
+                )
+            })?;
         } else {
             log::debug!(
-                "writing spirv-builder-cli source files into '{}'",
-                checkout.display()
+                "'{}' doesn't embed its `rustc` version, trusting '{}'",
+                dylib.display(),
+                rustc_version_path.display()
             );
-            self.write_source_files()?;
-            self.write_target_spec_files()?;
+        }
 
+        if !self.is_quiet {
             crate::user_output!(
-                "Compiling shader-specific `spirv-builder-cli` for {}\n",
-                self.shader_crate.display()
+                "Installing prebuilt `spirv-builder-cli` from {}\n",
+                prebuilt.display()
             );
+        }
+        Self::copy_prebuilt_artifacts(prebuilt, checkout)
+    }
 
-            let mut command = std::process::Command::new("cargo");
-            command
-                .current_dir(&checkout)
-                .arg(format!("+{}", spirv_version.channel))
-                .args(["build", "--release"])
-                .args(["--no-default-features"]);
+    /// The path of the prebuilt `spirv-builder-cli` in `prebuilt`. On Windows that's the
+    /// `.exe` that cargo builds, or otherwise one copied from an installation in the cache.
+    fn prebuilt_cli_path(prebuilt: &std::path::Path) -> std::path::PathBuf {
+        let exe_path = prebuilt.join("spirv-builder-cli").with_extension("exe");
+        if cfg!(target_os = "windows") && exe_path.is_file() {
+            exe_path
+        } else {
+            prebuilt.join("spirv-builder-cli")
+        }
+    }
 
-            command.args([
-                "--features",
-                &Self::get_required_spirv_builder_version(spirv_version.date)?,
-            ]);
+    /// Copy the prebuilt dylib, `spirv-builder-cli` and `rustc` version in `prebuilt` into the
+    /// checkout directory, and record the dylib's checksum.
+    fn copy_prebuilt_artifacts(
+        prebuilt: &std::path::Path,
+        checkout: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        std::fs::create_dir_all(checkout)?;
+        let dylib_path = checkout.join(dylib_filename());
+        let copies = [
+            (prebuilt.join(dylib_filename()), dylib_path.clone()),
+            (
+                Self::prebuilt_cli_path(prebuilt),
+                checkout.join("spirv-builder-cli"),
+            ),
+            (
+                prebuilt.join(RUSTC_VERSION_FILENAME),
+                checkout.join(RUSTC_VERSION_FILENAME),
+            ),
+        ];
+        for (from, to) in copies {
+            std::fs::copy(&from, to)
+                .with_context(|| format!("could not copy prebuilt '{}'", from.display()))?;
+        }
+        write_dylib_checksum(&dylib_path)
+    }
 
-            log::debug!("building artifacts with `{:?}`", command);
+    /// Whether the artifacts in `prebuilt` are already installed in the checkout directory,
+    /// ie the installed `rustc` version and dylib are the same as the prebuilt ones.
+    fn is_prebuilt_installed(
+        prebuilt: &std::path::Path,
+        checkout: &std::path::Path,
+    ) -> anyhow::Result<bool> {
+        let installed_dylib = checkout.join(dylib_filename());
+        if !installed_dylib.is_file() || !checkout.join("spirv-builder-cli").is_file() {
+            return Ok(false);
+        }
+        let read_rustc_version =
+            |dir: &std::path::Path| std::fs::read_to_string(dir.join(RUSTC_VERSION_FILENAME)).ok();
+        let installed_rustc_version = read_rustc_version(checkout);
+        if installed_rustc_version.is_none()
+            || installed_rustc_version != read_rustc_version(prebuilt)
+        {
+            return Ok(false);
+        }
+        Ok(sha256_hex(&installed_dylib)? == sha256_hex(&prebuilt.join(dylib_filename()))?)
+    }
+
+    /// The `cargo build` command for the `spirv-builder-cli` crate in the checkout.
+    fn build_command(
+        &self,
+        spirv_cli: &SpirvCli,
+        checkout: &std::path::Path,
+    ) -> anyhow::Result<std::process::Command> {
+        let mut command = spirv_cli.toolchain_command("cargo")?;
+        command
+            .current_dir(checkout)
+            .args(["build", "--profile", &self.backend_profile])
+            .args(["--no-default-features"]);
+        if let Some(jobs) = self.jobs {
+            command.args(["--jobs", &jobs.to_string()]);
+        }
+        if self.offline {
+            command.arg("--offline");
+        }
+        if self.backend_lockfile.is_some() {
+            command.arg("--locked");
+        }
+        for (key, value) in &self.cargo_config {
+            command.args(["--config", &format!("{key}={value}")]);
+        }
 
-            let output = command
-                .stdout(std::process::Stdio::inherit())
-                .stderr(std::process::Stdio::inherit())
-                .output()?;
-            anyhow::ensure!(output.status.success(), "...build error!");
+        command.args([
+            "--features",
+            &Self::get_required_spirv_builder_version(spirv_cli.date)?,
+        ]);
+        Ok(command)
+    }
 
-            if dylib_path.is_file() {
-                log::info!("successfully built {}", dylib_path.display());
-                std::fs::rename(&dylib_path, &dest_dylib_path)?;
-            } else {
-                log::error!("could not find {}", dylib_path.display());
-                anyhow::bail!("spirv-builder-cli build failed");
+    /// Build `spirv-builder-cli` and `rustc_codegen_spirv` in the checkout directory.
+    fn build_backend(
+        &self,
+        spirv_cli: &SpirvCli,
+        checkout: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        let mut command = self.build_command(spirv_cli, checkout)?;
+        log::debug!("building artifacts with `{:?}`", command);
+        let command_line = shell_command_line(&command);
+        if self.print_backend_build_command {
+            #[expect(
+                clippy::print_stdout,
+                reason = "The output is intended to be copied into a shell, so no crab prefix"
+            )]
+            {
+                println!("{command_line}");
             }
+        }
 
-            let cli_path = if cfg!(target_os = "windows") {
-                release.join("spirv-builder-cli").with_extension("exe")
-            } else {
-                release.join("spirv-builder-cli")
-            };
-            if cli_path.is_file() {
-                log::info!("successfully built {}", cli_path.display());
-                std::fs::rename(&cli_path, &dest_cli_path)?;
-            } else {
-                log::error!("could not find {}", cli_path.display());
-                log::debug!("contents of '{}':", release.display());
-                for maybe_entry in std::fs::read_dir(&release)? {
-                    let entry = maybe_entry?;
-                    log::debug!("{}", entry.file_name().to_string_lossy());
-                }
-                anyhow::bail!("spirv-builder-cli build failed");
+        // A terminal keeps cargo's colours and progress bar, and shows the error itself, so
+        // stderr is only teed, for its tail in the error, when it's eg a CI log.
+        command.stdout(std::process::Stdio::inherit());
+        if std::io::IsTerminal::is_terminal(&std::io::stderr()) {
+            let status = command.stderr(std::process::Stdio::inherit()).status()?;
+            anyhow::ensure!(
+                status.success(),
+                "...build error! To reproduce it, run:\n{command_line}"
+            );
+            return Ok(());
+        }
+
+        let mut child = command.stderr(std::process::Stdio::piped()).spawn()?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("could not capture the backend build's stderr")?;
+        let stderr_tail = crate::tee_stderr(stderr, std::io::stderr(), crate::STDERR_TAIL_LINES)?;
+        let status = child.wait()?;
+        anyhow::ensure!(
+            status.success(),
+            "...build error:\n{stderr_tail}\nTo reproduce it, run:\n{command_line}"
+        );
+        Ok(())
+    }
+
+    /// Copy the target spec files from the cache into the given directory.
+    fn copy_target_spec_files(&self, dir: &std::path::Path) -> anyhow::Result<()> {
+        self.write_target_spec_files()?;
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("could not create directory '{}'", dir.display()))?;
+        for (filename, _) in TARGET_SPECS {
+            let path = dir.join(filename);
+            std::fs::copy(target_spec_dir()?.join(filename), &path)
+                .with_context(|| format!("could not copy target spec to '{}'", path.display()))?;
+            #[expect(
+                clippy::print_stdout,
+                reason = "The output is intended to be captured by scripts, so no crab prefix"
+            )]
+            {
+                println!("{}", path.display());
             }
         }
-        Ok((dest_dylib_path, dest_cli_path))
+        Ok(())
+    }
+
+    /// The directory under `target/` that cargo puts a profile's artifacts in. The built-in
+    /// `dev` profile is the only one that doesn't use its own name.
+    fn profile_target_dir(profile: &str) -> &str {
+        if profile == "dev" {
+            "debug"
+        } else {
+            profile
+        }
+    }
+
+    /// Whether the `rustc` of the toolchain differs from the one that built the installed
+    /// artifacts. Artifacts that were installed without a record of their `rustc` are assumed
+    /// to be stale.
+    fn is_rustc_changed(
+        spirv_cli: &SpirvCli,
+        rustc_version_path: &std::path::Path,
+    ) -> anyhow::Result<bool> {
+        let Ok(installed_rustc_version) = std::fs::read_to_string(rustc_version_path) else {
+            log::info!(
+                "no record of the `rustc` that built the installed artifacts at '{}', rebuilding",
+                rustc_version_path.display()
+            );
+            return Ok(true);
+        };
+
+        let is_changed = installed_rustc_version != spirv_cli.rustc_version()?;
+        if is_changed {
+            log::info!(
+                "`rustc` for toolchain {} has changed since the artifacts were built, rebuilding",
+                spirv_cli.channel
+            );
+        }
+        Ok(is_changed)
     }
 
     /// The `spirv-builder` crate from the main `rust-gpu` repo hasn't always been setup to
@@ -308,3 +1328,574 @@ impl Install {
         .into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The `Cargo.toml` of the `spirv-builder-cli` crate, before any replacements.
+    const CARGO_TOML_TEMPLATE: &str = include_str!("../../spirv-builder-cli/Cargo.toml");
+
+    /// The `spirv-builder` dependency of each feature in the generated `Cargo.toml`, which must
+    /// parse as valid TOML.
+    fn spirv_builder_dependencies(source: &SpirvSource) -> Vec<toml::Table> {
+        let contents = Install::update_cargo_toml(CARGO_TOML_TEMPLATE, source);
+        let toml: toml::Table = toml::from_str(&contents).unwrap();
+        let dependencies = toml.get("dependencies").unwrap().as_table().unwrap();
+        ["spirv-builder-pre-cli", "spirv-builder-0_10"]
+            .into_iter()
+            .map(|feature| {
+                let dependency = dependencies.get(feature).unwrap().as_table().unwrap();
+                assert_eq!(
+                    Some("spirv-builder"),
+                    dependency.get("package").and_then(toml::Value::as_str)
+                );
+                dependency.clone()
+            })
+            .collect()
+    }
+
+    #[test_log::test]
+    fn cargo_toml_for_crates_io_source() {
+        let source = SpirvSource::CratesIO("v0.9.0".to_owned());
+        for dependency in spirv_builder_dependencies(&source) {
+            assert_eq!(
+                Some("=0.9.0"),
+                dependency.get("version").and_then(toml::Value::as_str)
+            );
+            assert!(!dependency.contains_key("git"));
+            assert!(!dependency.contains_key("rev"));
+        }
+    }
+
+    #[test_log::test]
+    fn cargo_toml_for_git_source() {
+        let source = SpirvSource::Git {
+            url: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+            rev: "82a0f69".to_owned(),
+        };
+        for dependency in spirv_builder_dependencies(&source) {
+            assert_eq!(
+                Some("https://github.com/Rust-GPU/rust-gpu"),
+                dependency.get("git").and_then(toml::Value::as_str)
+            );
+            assert_eq!(
+                Some("82a0f69"),
+                dependency.get("rev").and_then(toml::Value::as_str)
+            );
+            assert!(!dependency.contains_key("version"));
+        }
+    }
+
+    #[test_log::test]
+    fn cargo_toml_for_windows_path_source() {
+        let path = r"C:\Users\shader dev\rust-gpu";
+        let source = SpirvSource::Path((path.to_owned(), "v0.9.0".to_owned()));
+        for dependency in spirv_builder_dependencies(&source) {
+            assert_eq!(
+                Some(path),
+                dependency.get("path").and_then(toml::Value::as_str)
+            );
+        }
+    }
+
+    #[test_log::test]
+    fn cargo_toml_for_path_source() {
+        let source = SpirvSource::Path(("/path/to/rust-gpu".to_owned(), "v0.9.0".to_owned()));
+        for dependency in spirv_builder_dependencies(&source) {
+            assert_eq!(
+                Some("/path/to/rust-gpu"),
+                dependency.get("path").and_then(toml::Value::as_str)
+            );
+            assert_eq!(
+                Some("0.9.0"),
+                dependency.get("version").and_then(toml::Value::as_str)
+            );
+            assert!(!dependency.contains_key("git"));
+        }
+    }
+
+    #[test_log::test]
+    fn cargo_toml_for_path_source_outside_the_repo_layout() {
+        let repo = crate::test::fixture_dir("rust-gpu-with-moved-spirv-std");
+        let spirv_builder = repo.join("crates").join("spirv-builder");
+        let spirv_std = repo.join("shaders").join("std").join("spirv-std");
+        std::fs::create_dir_all(&spirv_builder).unwrap();
+        std::fs::create_dir_all(&spirv_std).unwrap();
+
+        let source = SpirvSource::Path((spirv_std.display().to_string(), "v0.9.0".to_owned()));
+        for dependency in spirv_builder_dependencies(&source) {
+            assert_eq!(
+                Some(spirv_builder.display().to_string().as_str()),
+                dependency.get("path").and_then(toml::Value::as_str)
+            );
+        }
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn profile_target_dirs() {
+        assert_eq!("release", Install::profile_target_dir("release"));
+        assert_eq!("debug", Install::profile_target_dir("dev"));
+        assert_eq!("profiling", Install::profile_target_dir("profiling"));
+    }
+
+    #[test_log::test]
+    fn jobs_from_params() {
+        let args = ["target/debug/cargo-gpu", "install", "--jobs", "2"];
+        if let crate::Cli {
+            command: crate::Command::Install(install),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(args)
+        {
+            assert_eq!(Some(2), install.jobs);
+        } else {
+            panic!("was not an install command");
+        }
+    }
+
+    #[test_log::test]
+    fn resolving_missing_shader_crates() {
+        let missing = crate::test::fixture_dir("missing-shader-crates").join("missing");
+        let args = [
+            "target/debug/cargo-gpu",
+            "install",
+            "--spirv-source-only",
+            "--shader-crate",
+            missing.to_str().unwrap(),
+        ];
+        if let crate::Cli {
+            command: crate::Command::Install(install),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(args)
+        {
+            let resolution = install.resolve();
+            assert_eq!(ResolutionStatus::Unresolvable, resolution.status);
+            let unresolvable_exit_code: i32 = 11;
+            assert_eq!(unresolvable_exit_code, resolution.status.exit_code());
+            let json = serde_json::to_value(&resolution).unwrap();
+            assert_eq!(
+                Some("unresolvable"),
+                json.get("status").and_then(serde_json::Value::as_str)
+            );
+            assert!(json.get("source").is_none());
+            assert!(json.get("error").is_some());
+        } else {
+            panic!("was not an install command");
+        }
+        assert!(ResolutionStatus::NotInstalled > ResolutionStatus::Installed);
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn offline_from_params() {
+        let args = ["target/debug/cargo-gpu", "build", "--offline"];
+        if let crate::Cli {
+            command: crate::Command::Build(build),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(args)
+        {
+            assert!(build.install.offline);
+        } else {
+            panic!("was not a build command");
+        }
+    }
+
+    #[test_log::test]
+    fn backend_lockfile_from_params() {
+        let args = [
+            "target/debug/cargo-gpu",
+            "install",
+            "--backend-lockfile",
+            "backend.lock",
+        ];
+        if let crate::Cli {
+            command: crate::Command::Install(install),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(args)
+        {
+            assert_eq!(
+                Some(std::path::PathBuf::from("backend.lock")),
+                install.backend_lockfile
+            );
+        } else {
+            panic!("was not an install command");
+        }
+    }
+
+    #[test_log::test]
+    fn prebuilt_rustc_versions() {
+        let nightly =
+            "rustc 1.80.0-nightly (3105b3cb6 2024-04-24)\nhost: x86_64-unknown-linux-gnu\n";
+        ensure_same_rustc_version(nightly, nightly.trim(), "nightly-2024-04-24").unwrap();
+        let error = ensure_same_rustc_version(
+            "rustc 1.79.0-nightly (b8d7a3e0c 2024-04-13)\n",
+            nightly,
+            "nightly-2024-04-24",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("nightly-2024-04-24"), "{error}");
+        assert!(error.contains("1.79.0-nightly"), "{error}");
+    }
+
+    #[test_log::test]
+    fn dylib_checksums() {
+        let dir = crate::test::fixture_dir("dylib-checksums");
+        let dylib = dir.join(dylib_filename());
+        std::fs::write(&dylib, "abc").unwrap();
+
+        assert!(is_dylib_intact(&dylib).unwrap());
+        write_dylib_checksum(&dylib).unwrap();
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            std::fs::read_to_string(dir.join(DYLIB_CHECKSUM_FILENAME)).unwrap()
+        );
+        assert!(is_dylib_intact(&dylib).unwrap());
+        std::fs::write(&dylib, "abd").unwrap();
+        assert!(!is_dylib_intact(&dylib).unwrap());
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn source_alias_from_params() {
+        let args = [
+            "target/debug/cargo-gpu",
+            "install",
+            "--spirv-builder-source-alias",
+            "rust-gpu",
+            "--spirv-builder-version",
+            "82a0f69",
+        ];
+        if let crate::Cli {
+            command: crate::Command::Install(install),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(args)
+        {
+            assert_eq!(
+                Some("https://github.com/Rust-GPU/rust-gpu"),
+                install.spirv_builder_source_alias.as_deref()
+            );
+        } else {
+            panic!("was not an install command");
+        }
+    }
+
+    #[test_log::test]
+    fn install_steps() {
+        assert_eq!(
+            "already installed, nothing to build",
+            InstallStep::UseInstalled.to_string()
+        );
+        assert_eq!(
+            "would be installed from prebuilt 'shared/backend'",
+            InstallStep::InstallPrebuilt("shared/backend".into()).to_string()
+        );
+        assert_eq!(
+            "would be built, as it isn't installed yet",
+            InstallStep::Build("it isn't installed yet").to_string()
+        );
+    }
+
+    #[test_log::test]
+    fn cargo_config_overrides() {
+        assert_eq!(
+            Ok(("net.retry".to_owned(), "5".to_owned())),
+            parse_cargo_config("net.retry=5")
+        );
+        assert_eq!(
+            Ok(("http.user-agent".to_owned(), "a=b".to_owned())),
+            parse_cargo_config("http.user-agent=a=b")
+        );
+        parse_cargo_config("net.retry").unwrap_err();
+        parse_cargo_config("=5").unwrap_err();
+        parse_cargo_config("net..retry=5").unwrap_err();
+        parse_cargo_config("net retry=5").unwrap_err();
+
+        assert_eq!("CARGO_NET_RETRY", cargo_config_env("net.retry"));
+        assert_eq!("CARGO_HTTP_USER_AGENT", cargo_config_env("http.user-agent"));
+    }
+
+    /// Parse the arguments of `cargo gpu install`.
+    fn install_from_params(params: &[&str]) -> Install {
+        let args = ["target/debug/cargo-gpu", "install"].iter().chain(params);
+        if let crate::Cli {
+            command: crate::Command::Install(install),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(args)
+        {
+            install
+        } else {
+            panic!("was not an install command");
+        }
+    }
+
+    #[test_log::test]
+    fn boolish_env_vars_from_params() {
+        crate::test::with_env(
+            "install::test::boolish_env_vars_from_params",
+            &[
+                ("CARGO_GPU_AUTO_INSTALL_RUST_TOOLCHAIN", "1"),
+                ("CARGO_NET_OFFLINE", "yes"),
+            ],
+            || {
+                let install = install_from_params(&[]);
+                assert!(install.auto_install_rust_toolchain);
+                assert!(install.offline);
+            },
+        );
+        crate::test::with_env(
+            "install::test::boolish_env_vars_from_params",
+            &[
+                ("CARGO_GPU_AUTO_INSTALL_RUST_TOOLCHAIN", "0"),
+                ("CARGO_NET_OFFLINE", "false"),
+            ],
+            || {
+                let install = install_from_params(&[]);
+                assert!(!install.auto_install_rust_toolchain);
+                assert!(!install.offline);
+            },
+        );
+    }
+
+    /// Write a fake backend installation, with a dylib containing `dylib`, to `dir`.
+    fn write_fake_backend(dir: &std::path::Path, dylib: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(dylib_filename()), dylib).unwrap();
+        std::fs::write(dir.join("spirv-builder-cli"), "cli").unwrap();
+        std::fs::write(
+            dir.join(RUSTC_VERSION_FILENAME),
+            "rustc 1.80.0-nightly (3105b3cb6 2024-04-24)",
+        )
+        .unwrap();
+    }
+
+    #[test_log::test]
+    fn planning_rebuilds_of_installs() {
+        let checkout = SpirvCli::for_tests().checkout_path().unwrap();
+        drop(std::fs::remove_dir_all(&checkout));
+        let install = install_from_params(&[]);
+        let step = |with: &Install| with.plan(SpirvCli::for_tests(), false).unwrap().step;
+
+        assert_eq!(InstallStep::Build("it isn't installed yet"), step(&install));
+        write_fake_backend(&checkout, "dylib");
+        assert_eq!(InstallStep::UseInstalled, step(&install));
+        assert_eq!(
+            InstallStep::Build("`--force-spirv-cli-rebuild` was given"),
+            step(&install_from_params(&["--force-spirv-cli-rebuild"]))
+        );
+
+        let dylib = checkout.join(dylib_filename());
+        write_dylib_checksum(&dylib).unwrap();
+        assert_eq!(InstallStep::UseInstalled, step(&install));
+        std::fs::write(&dylib, "corrupted dylib").unwrap();
+        assert_eq!(
+            InstallStep::Build("the installed dylib doesn't match its checksum"),
+            step(&install)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test_log::test]
+    fn planning_rebuilds_when_rustc_changes() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let bin_dir = crate::test::fixture_dir("fake-toolchain-bin");
+        let rustc = bin_dir.join("rustc");
+        let write_rustc = |version: &str| {
+            std::fs::write(&rustc, format!("#!/bin/sh\nprintf '{version}'\n")).unwrap();
+            std::fs::set_permissions(&rustc, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        let mut spirv_cli = SpirvCli::for_tests();
+        spirv_cli.source = SpirvSource::Path(("/path/to/rust-gpu".to_owned(), "v0.9.0".to_owned()));
+        spirv_cli.toolchain_bin_dir = Some(bin_dir);
+        let checkout = spirv_cli.checkout_path().unwrap();
+        drop(std::fs::remove_dir_all(&checkout));
+        write_fake_backend(&checkout, "dylib");
+        let install = install_from_params(&[]);
+        let step =
+            |can_run_rustc: bool| install.plan(spirv_cli.clone(), can_run_rustc).unwrap().step;
+
+        write_rustc("rustc 1.80.0-nightly (3105b3cb6 2024-04-24)");
+        assert_eq!(InstallStep::UseInstalled, step(true));
+        write_rustc("rustc 1.81.0-nightly (7120fdac7 2024-06-01)");
+        assert_eq!(
+            InstallStep::Build("the toolchain's `rustc` has changed since it was built"),
+            step(true)
+        );
+        assert_eq!(InstallStep::UseInstalled, step(false));
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn reading_embedded_rustc_versions() {
+        let dir = crate::test::fixture_dir("embedded-rustc-versions");
+        let dylib = dir.join(dylib_filename());
+        std::fs::write(
+            &dylib,
+            b"\x7fELF\0\0rustc version 1.80.0-nightly (3105b3cb6 2024-04-24)\0clang LLVM",
+        )
+        .unwrap();
+        assert_eq!(
+            Some("rustc 1.80.0-nightly (3105b3cb6 2024-04-24)".to_owned()),
+            embedded_rustc_version(&dylib).unwrap()
+        );
+        std::fs::write(&dylib, "not a dylib").unwrap();
+        assert_eq!(None, embedded_rustc_version(&dylib).unwrap());
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn planning_prebuilt_installs() {
+        let prebuilt = crate::test::fixture_dir("prebuilt-backend");
+        write_fake_backend(&prebuilt, "prebuilt dylib");
+        let prebuilt_arg = prebuilt.to_str().unwrap();
+        let install = install_from_params(&["--prebuilt-backend", prebuilt_arg]);
+        let checkout = SpirvCli::for_tests().checkout_path().unwrap();
+        drop(std::fs::remove_dir_all(&checkout));
+
+        let step = |with: &Install| with.plan(SpirvCli::for_tests(), false).unwrap().step;
+        assert_eq!(
+            InstallStep::InstallPrebuilt(prebuilt.clone()),
+            step(&install)
+        );
+
+        Install::copy_prebuilt_artifacts(&prebuilt, &checkout).unwrap();
+        assert!(is_dylib_intact(&checkout.join(dylib_filename())).unwrap());
+        assert_eq!(InstallStep::UseInstalled, step(&install));
+
+        let forced = install_from_params(&[
+            "--prebuilt-backend",
+            prebuilt_arg,
+            "--force-spirv-cli-rebuild",
+        ]);
+        assert_eq!(
+            InstallStep::InstallPrebuilt(prebuilt.clone()),
+            step(&forced)
+        );
+
+        std::fs::write(prebuilt.join(dylib_filename()), "newer prebuilt dylib").unwrap();
+        assert_eq!(
+            InstallStep::InstallPrebuilt(prebuilt.clone()),
+            step(&install)
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn building_with_backend_lockfile() {
+        let dir = crate::test::fixture_dir("backend-lockfile");
+        let checkout = dir.join("checkout");
+        std::fs::create_dir_all(&checkout).unwrap();
+        let lockfile = dir.join("backend.lock");
+        std::fs::write(&lockfile, "version = 4\n").unwrap();
+        let install = install_from_params(&["--backend-lockfile", lockfile.to_str().unwrap()]);
+
+        let command = install
+            .build_command(&SpirvCli::for_tests(), &checkout)
+            .unwrap();
+        assert!(command.get_args().any(|arg| arg == "--locked"));
+        let unlocked = install_from_params(&[])
+            .build_command(&SpirvCli::for_tests(), &checkout)
+            .unwrap();
+        assert!(!unlocked.get_args().any(|arg| arg == "--locked"));
+
+        install.copy_backend_lockfile(&checkout).unwrap();
+        assert_eq!(
+            "version = 4\n",
+            std::fs::read_to_string(checkout.join("Cargo.lock")).unwrap()
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn planning_rebuilds_for_backend_lockfile() {
+        let lockfile = crate::test::fixture_dir("planned-backend-lockfile").join("backend.lock");
+        std::fs::write(&lockfile, "version = 4\n").unwrap();
+        let install = install_from_params(&["--backend-lockfile", lockfile.to_str().unwrap()]);
+        let checkout = SpirvCli::for_tests().checkout_path().unwrap();
+        drop(std::fs::remove_dir_all(&checkout));
+        write_fake_backend(&checkout, "dylib");
+
+        let step = |with: &Install| with.plan(SpirvCli::for_tests(), false).unwrap().step;
+        assert_eq!(
+            InstallStep::Build("it wasn't built with `--backend-lockfile`"),
+            step(&install)
+        );
+        install.write_backend_lockfile_checksum(&checkout).unwrap();
+        assert_eq!(InstallStep::UseInstalled, step(&install));
+
+        std::fs::write(&lockfile, "version = 3\n").unwrap();
+        assert_eq!(
+            InstallStep::Build("it wasn't built with `--backend-lockfile`"),
+            step(&install)
+        );
+
+        let unlocked = install_from_params(&[]);
+        assert_eq!(InstallStep::UseInstalled, step(&unlocked));
+        unlocked.write_backend_lockfile_checksum(&checkout).unwrap();
+        assert!(!checkout.join(BACKEND_LOCKFILE_CHECKSUM_FILENAME).exists());
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn build_command_with_toolchain_bin_dir() {
+        let dir = std::path::PathBuf::from("toolchain").join("bin");
+        let mut spirv_cli = SpirvCli::for_tests();
+        spirv_cli.toolchain_bin_dir = Some(dir.clone());
+        let command = install_from_params(&[])
+            .build_command(&spirv_cli, std::path::Path::new("checkout"))
+            .unwrap();
+
+        let envs = command
+            .get_envs()
+            .filter_map(|(key, value)| Some((key.to_str()?, std::path::PathBuf::from(value?))))
+            .collect::<std::collections::HashMap<_, _>>();
+        assert_eq!(
+            Some(&dir.join(format!("rustc{}", std::env::consts::EXE_SUFFIX))),
+            envs.get("RUSTC")
+        );
+        assert_eq!(
+            Some(dir),
+            envs.get("PATH")
+                .and_then(|path| std::env::split_paths(path).next())
+        );
+    }
+
+    #[test_log::test]
+    fn shell_command_lines() {
+        let mut command = std::process::Command::new("cargo");
+        command
+            .current_dir("/cache/spirv builder")
+            .env("RUSTFLAGS", "-C debuginfo=0")
+            .args([
+                "+nightly-2024-04-24",
+                "build",
+                "--features",
+                "spirv-builder-0_10",
+            ])
+            .arg("it's");
+        assert_eq!(
+            "cd '/cache/spirv builder' && RUSTFLAGS='-C debuginfo=0' \
+             cargo +nightly-2024-04-24 build --features spirv-builder-0_10 'it'\\''s'",
+            shell_command_line(&command)
+        );
+    }
+
+    #[test_log::test]
+    fn reading_rev_files() {
+        let dir = crate::test::fixture_dir("rev-files");
+        let rev_file = dir.join("rust-gpu.rev");
+
+        std::fs::write(&rev_file, "82a0f69\n").unwrap();
+        assert_eq!("82a0f69", Install::read_rev_file(&rev_file).unwrap());
+
+        for contents in ["", "  \n", "82a0f69\n60dcb82\n", "82a0f69 60dcb82"] {
+            std::fs::write(&rev_file, contents).unwrap();
+            Install::read_rev_file(&rev_file).unwrap_err();
+        }
+
+        Install::read_rev_file(&dir.join("missing.rev")).unwrap_err();
+        crate::test::tests_teardown();
+    }
+}
@@ -54,16 +54,21 @@ use anyhow::Context as _;
 
 use build::Build;
 use clap::Parser as _;
+use doctor::Doctor;
 use install::Install;
 use show::Show;
 use toml::Toml;
+use uninstall::Uninstall;
 
 mod build;
+mod doctor;
 mod install;
+mod reflect;
 mod show;
 mod spirv_cli;
 mod spirv_source;
 mod toml;
+mod uninstall;
 
 /// Central function to write to the user.
 #[macro_export]
@@ -77,21 +82,81 @@ macro_rules! user_output {
         )]
         use std::io::Write as _;
 
-        #[expect(
-            clippy::non_ascii_literal,
-            reason = "CRAB GOOD. CRAB IMPORTANT."
-        )]
-        {
-            print!("🦀 ");
+        if !$crate::is_quiet() {
+            print!("{}", $crate::user_output_prefix());
+            print!($($args)*);
+            std::io::stdout().flush().unwrap();
         }
-        print!($($args)*);
-        std::io::stdout().flush().unwrap();
    }
 }
 
+/// The prefix of each line of [`user_output!`]. It can be replaced with
+/// `CARGO_GPU_OUTPUT_PREFIX`, or swapped for a plain `>` by setting `CARGO_GPU_NO_EMOJI`, for
+/// terminals and log files that mangle emoji.
+fn user_output_prefix() -> String {
+    output_prefix(
+        std::env::var_os("CARGO_GPU_OUTPUT_PREFIX").as_deref(),
+        std::env::var_os("CARGO_GPU_NO_EMOJI").as_deref(),
+    )
+}
+
+/// The environment variable that silences [`user_output!`], which `--quiet` also sets.
+const QUIET_ENV: &str = "CARGO_GPU_QUIET";
+
+/// Whether [`user_output!`] is silenced, by `--quiet` or `CARGO_GPU_QUIET`.
+fn is_quiet() -> bool {
+    is_env_flag_set(std::env::var_os(QUIET_ENV).as_deref())
+}
+
+/// The environment variable that makes cargo work offline, which `--offline` also sets so that
+/// every `cargo` that we spawn, including the one run by `spirv-builder`, stays offline.
+const OFFLINE_ENV: &str = "CARGO_NET_OFFLINE";
+
+/// For `--offline`, make every `cargo` that we spawn from now on work offline too, starting
+/// with the `cargo tree` that resolves the `rust-gpu` source.
+fn set_offline_env(install: &Install) {
+    if install.offline {
+        std::env::set_var(OFFLINE_ENV, "true");
+    }
+}
+
+/// Whether network access is forbidden, by `--offline` or `CARGO_NET_OFFLINE`.
+fn is_offline() -> bool {
+    std::env::var_os(OFFLINE_ENV).is_some_and(|value| value == "true")
+}
+
+/// Whether a flag-like environment variable is set to something other than empty or "0".
+fn is_env_flag_set(value: Option<&std::ffi::OsStr>) -> bool {
+    value.is_some_and(|set_value| !set_value.is_empty() && set_value != "0")
+}
+
+/// The [`user_output_prefix`], given the values of its environment variables.
+fn output_prefix(
+    custom_prefix: Option<&std::ffi::OsStr>,
+    no_emoji: Option<&std::ffi::OsStr>,
+) -> String {
+    if let Some(prefix) = custom_prefix {
+        return if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", prefix.to_string_lossy())
+        };
+    }
+
+    if is_env_flag_set(no_emoji) {
+        return "> ".to_owned();
+    }
+
+    #[expect(clippy::non_ascii_literal, reason = "CRAB GOOD. CRAB IMPORTANT.")]
+    "🦀 ".to_owned()
+}
+
 fn main() {
+    // Don't clobber a backtrace setting that the user has explicitly chosen.
     #[cfg(debug_assertions)]
-    std::env::set_var("RUST_BACKTRACE", "1");
+    if std::env::var_os("RUST_BACKTRACE").is_none() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+    }
 
     env_logger::builder().init();
 
@@ -120,10 +185,72 @@ fn run() -> anyhow::Result<()> {
     log::trace!("args: {args:?}");
     let cli = Cli::parse_from(args);
 
+    // Every use of the cache directory, including by sub-processes that we spawn, reads the
+    // override from the environment.
+    if let Some(dir) = &cli.cache_dir {
+        std::env::set_var(CACHE_DIR_ENV, dir);
+    }
+    if cli.quiet {
+        std::env::set_var(QUIET_ENV, "1");
+    }
+    if let Some(install) = cli.command.install() {
+        set_offline_env(install);
+    }
+
     match cli.command {
+        Command::Install(install) if install.spirv_source_only => {
+            log::debug!("resolving with arguments: {install:#?}");
+            let mut exit_code: i32 = 0;
+            for shader_crate_install in install.for_each_shader_crate() {
+                let resolution = shader_crate_install.resolve();
+                exit_code = exit_code.max(resolution.status.exit_code());
+                #[expect(
+                    clippy::print_stdout,
+                    reason = "The output is intended to be read by tools, so no crab prefix"
+                )]
+                {
+                    println!("{}", serde_json::to_string(&resolution)?);
+                }
+            }
+            #[expect(
+                clippy::exit,
+                reason = "The exit code is how `--spirv-source-only` reports its result"
+            )]
+            std::process::exit(exit_code);
+        }
+        Command::Install(install) if install.print_toolchain_install_commands => {
+            let mut commands = Vec::new();
+            for shader_crate_install in install.for_each_shader_crate() {
+                let spirv_cli =
+                    shader_crate_install.spirv_cli(shader_crate_install.shader_crate())?;
+                for command in spirv_cli.toolchain_install_commands()? {
+                    if !commands.contains(&command) {
+                        commands.push(command);
+                    }
+                }
+            }
+            #[expect(
+                clippy::print_stdout,
+                reason = "The output is intended to be run in a shell, so no crab prefix"
+            )]
+            for command in commands {
+                println!("{command}");
+            }
+        }
+        Command::Install(install) if install.dry_run => {
+            for shader_crate_install in install.for_each_shader_crate() {
+                shader_crate_install.dry_run()?;
+            }
+        }
         Command::Install(install) => {
             log::debug!("installing with arguments: {install:#?}");
-            let (_, _) = install.run()?;
+            for shader_crate_install in install.for_each_shader_crate() {
+                if shader_crate_install.only_toolchain {
+                    shader_crate_install.install_toolchain()?;
+                } else {
+                    let (_, _) = shader_crate_install.run()?;
+                }
+            }
         }
         Command::Build(mut build) => {
             log::debug!("building with arguments: {build:#?}");
@@ -134,6 +261,8 @@ fn run() -> anyhow::Result<()> {
             toml.run()?;
         }
         Command::Show(show) => show.run()?,
+        Command::Doctor(doctor) => doctor.run()?,
+        Command::Uninstall(uninstall) => uninstall.run()?,
         Command::DumpUsage => dump_full_usage_for_readme()?,
     };
 
@@ -147,7 +276,7 @@ enum Command {
     Install(Install),
 
     /// Compile a shader crate to SPIR-V.
-    Build(Build),
+    Build(Box<Build>),
 
     /// Compile a shader crate according to the `cargo gpu build` parameters
     /// found in the given toml file.
@@ -156,6 +285,14 @@ enum Command {
     /// Show some useful values.
     Show(Show),
 
+    /// Check that everything needed to build a shader crate is in place, and optionally fix
+    /// what can be fixed.
+    Doctor(Doctor),
+
+    /// Remove the cached installation of one `rust-gpu` source and toolchain, so that the next
+    /// build installs it afresh.
+    Uninstall(Uninstall),
+
     /// A hidden command that can be used to recursively print out all the subcommand help messages:
     ///   `cargo gpu dump-usage`
     /// Useful for updating the README.
@@ -163,19 +300,44 @@ enum Command {
     DumpUsage,
 }
 
+impl Command {
+    /// The install flags of the command, if it resolves `rust-gpu` with them.
+    fn install(&self) -> Option<&Install> {
+        match self {
+            Self::Install(install) => Some(install),
+            Self::Build(build) => Some(&build.install),
+            Self::Show(show) => show.install(),
+            Self::Doctor(doctor) => Some(&doctor.install),
+            Self::Uninstall(uninstall) => Some(&uninstall.install),
+            Self::Toml(_) | Self::DumpUsage => None,
+        }
+    }
+}
+
 #[derive(clap::Parser)]
 #[clap(author, version, about, subcommand_required = true)]
 pub(crate) struct Cli {
     /// The command to run.
     #[clap(subcommand)]
     command: Command,
+
+    /// Use this directory instead of the user's cache directory for the installed `rust-gpu`
+    /// backends, target specs and `rust-gpu` checkouts. Useful where the home directory isn't
+    /// writable or persistent, like on CI.
+    #[clap(long, global = true, env = "CARGO_GPU_CACHE_DIR")]
+    cache_dir: Option<std::path::PathBuf>,
+
+    /// Don't print `cargo gpu`'s own messages, only the output that a command is asked for,
+    /// like the paths of `--print-spv-paths`. Errors are still printed.
+    #[clap(long, short, global = true)]
+    quiet: bool,
 }
 
+/// The environment variable that overrides the cache directory, which `--cache-dir` also sets.
+const CACHE_DIR_ENV: &str = "CARGO_GPU_CACHE_DIR";
+
 fn cache_dir() -> anyhow::Result<std::path::PathBuf> {
-    let dir = directories::BaseDirs::new()
-        .with_context(|| "could not find the user home directory")?
-        .cache_dir()
-        .join("rust-gpu");
+    let dir = base_cache_dir(std::env::var_os(CACHE_DIR_ENV).as_deref())?;
 
     Ok(if cfg!(test) {
         let thread_id = std::thread::current().id();
@@ -186,13 +348,118 @@ fn cache_dir() -> anyhow::Result<std::path::PathBuf> {
     })
 }
 
+/// The cache directory, which is the `CARGO_GPU_CACHE_DIR` override if it's set and not empty.
+fn base_cache_dir(maybe_override: Option<&std::ffi::OsStr>) -> anyhow::Result<std::path::PathBuf> {
+    match maybe_override {
+        Some(dir) if !dir.is_empty() => Ok(std::path::PathBuf::from(dir)),
+        _ => Ok(directories::BaseDirs::new()
+            .with_context(|| "could not find the user home directory")?
+            .cache_dir()
+            .join("rust-gpu")),
+    }
+}
+
+/// Location of the target spec metadata files, which may not exist yet
+fn target_spec_path() -> anyhow::Result<std::path::PathBuf> {
+    Ok(cache_dir()?.join("target-specs"))
+}
+
 /// Location of the target spec metadata files
 fn target_spec_dir() -> anyhow::Result<std::path::PathBuf> {
-    let dir = cache_dir()?.join("target-specs");
+    let dir = target_spec_path()?;
     std::fs::create_dir_all(&dir)?;
     Ok(dir)
 }
 
+/// Returns the canonical path to the `Cargo.toml` of a crate or workspace, given either its
+/// directory or the manifest file itself. This matches how cargo resolves `--manifest-path`.
+fn cargo_manifest_path(path: &std::path::Path) -> anyhow::Result<std::path::PathBuf> {
+    let manifest_path = if path.is_dir() {
+        path.join("Cargo.toml")
+    } else {
+        path.to_path_buf()
+    };
+    anyhow::ensure!(
+        manifest_path.is_file(),
+        "toml file '{}' is not a file",
+        manifest_path.display()
+    );
+    manifest_path.canonicalize().with_context(|| {
+        format!(
+            "could not get absolute path to '{}'",
+            manifest_path.display()
+        )
+    })
+}
+
+/// The most lines of a failed command's stderr that [`stderr_tail`] keeps.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// The last lines of a failed command's stderr, decoded lossily, to include in its error. The
+/// actual error is usually at the end, after eg progress messages.
+fn stderr_tail(stderr: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stderr);
+    let lines = text.trim_end().lines().collect::<Vec<_>>();
+    let tail = lines
+        .get(lines.len().saturating_sub(STDERR_TAIL_LINES)..)
+        .unwrap_or_default();
+    if tail.len() < lines.len() {
+        format!("...\n{}", tail.join("\n"))
+    } else {
+        tail.join("\n")
+    }
+}
+
+/// Forward a running command's `stderr` to `forward` line by line, as it's written, so that
+/// its progress is still shown, and return its last `max_lines` lines, eg
+/// [`STDERR_TAIL_LINES`] to include in its error as [`stderr_tail`] does.
+fn tee_stderr(
+    stderr: impl std::io::Read,
+    mut forward: impl std::io::Write,
+    max_lines: usize,
+) -> std::io::Result<String> {
+    use std::io::BufRead as _;
+
+    let mut tail = std::collections::VecDeque::new();
+    let mut is_truncated = false;
+    for read_line in std::io::BufReader::new(stderr).split(b'\n') {
+        let line = read_line?;
+        forward.write_all(&line)?;
+        forward.write_all(b"\n")?;
+        if tail.len() == max_lines {
+            tail.pop_front();
+            is_truncated = true;
+        }
+        tail.push_back(String::from_utf8_lossy(&line).trim_end().to_owned());
+    }
+    let lines = Vec::from(tail).join("\n");
+    Ok(if is_truncated {
+        format!("...\n{lines}")
+    } else {
+        lines
+    })
+}
+
+/// Returns the root directory of the workspace that contains the given directory, as found by
+/// `cargo locate-project --workspace`.
+fn cargo_workspace_root(dir: &std::path::Path) -> anyhow::Result<std::path::PathBuf> {
+    let output = std::process::Command::new("cargo")
+        .current_dir(dir)
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "could not find the cargo workspace of '{}':\n{}",
+        dir.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let workspace_manifest = std::path::PathBuf::from(String::from_utf8(output.stdout)?.trim());
+    Ok(workspace_manifest
+        .parent()
+        .context("workspace `Cargo.toml` has no parent directory")?
+        .to_path_buf())
+}
+
 /// Convenience function for internal use. Dumps all the CLI usage instructions. Useful for
 /// updating the README.
 fn dump_full_usage_for_readme() -> anyhow::Result<()> {
@@ -252,18 +519,196 @@ fn to_dirname(text: &str) -> String {
 
 #[cfg(test)]
 mod test {
-    use crate::cache_dir;
+    use crate::{cache_dir, cargo_manifest_path};
 
     pub fn shader_crate_template_path() -> std::path::PathBuf {
         let project_base = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         project_base.join("../shader-crate-template")
     }
 
+    /// Set in the child process that [`with_env`] runs a test in, to the `envs` that it's for.
+    const WITH_ENV_CHILD: &str = "CARGO_GPU_TEST_WITH_ENV_CHILD";
+
+    /// Set in the child process that [`with_env`] runs a test in, to the directory of the
+    /// parent's [`fixture_dir`]s, which the child shares.
+    const WITH_ENV_FIXTURES: &str = "CARGO_GPU_TEST_WITH_ENV_FIXTURES";
+
+    /// The directory of the test's [`fixture_dir`]s, in its thread's [`cache_dir`]. The process
+    /// is part of it too, so that concurrent test runs don't share it.
+    fn fixtures_dir() -> std::path::PathBuf {
+        if let Some(dir) = std::env::var_os(WITH_ENV_FIXTURES) {
+            return dir.into();
+        }
+        cache_dir()
+            .unwrap()
+            .join(format!("fixtures-{}", std::process::id()))
+    }
+
+    /// An empty directory `name` for a test's fixture files, which [`tests_teardown`] removes.
+    ///
+    /// In the child process of [`with_env`] it's the parent's directory, as it was already
+    /// created, and possibly filled, by the parent.
+    pub fn fixture_dir(name: &str) -> std::path::PathBuf {
+        let dir = fixtures_dir().join(name);
+        if std::env::var_os(WITH_ENV_CHILD).is_none() {
+            drop(std::fs::remove_dir_all(&dir));
+            std::fs::create_dir_all(&dir).unwrap();
+        }
+        dir
+    }
+
+    /// Run `check` with the environment variables `envs` set, in a child process that runs the
+    /// test `test_name` (its full path, eg `build::test::env_vars_from_params`) again. Setting
+    /// them in the test process itself would race with the other tests reading the environment,
+    /// which includes every test that parses arguments.
+    ///
+    /// The child only runs the `check` of the `with_env` call that started it, so a test can
+    /// make several calls.
+    pub fn with_env(test_name: &str, envs: &[(&str, &str)], check: impl FnOnce()) {
+        let label = format!("{envs:?}");
+        if let Some(child_label) = std::env::var_os(WITH_ENV_CHILD) {
+            if child_label == label.as_str() {
+                check();
+            }
+            return;
+        }
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([test_name, "--exact", "--nocapture"])
+            .env(WITH_ENV_CHILD, &label)
+            .env(WITH_ENV_FIXTURES, fixtures_dir())
+            .envs(envs.iter().copied())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "`{test_name}` failed with {envs:?}:\n{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
     pub fn tests_teardown() {
+        // The child process of `with_env` leaves the cleanup to its parent, whose thread's
+        // cache directory can be the same as its own.
+        if std::env::var_os(WITH_ENV_CHILD).is_some() {
+            return;
+        }
         let cache_dir = cache_dir().unwrap();
         if !cache_dir.exists() {
             return;
         }
         std::fs::remove_dir_all(cache_dir).unwrap();
     }
+
+    #[test_log::test]
+    fn output_prefixes() {
+        use crate::output_prefix;
+        use std::ffi::OsStr;
+
+        assert_eq!("\u{1f980} ", output_prefix(None, None));
+        assert_eq!("\u{1f980} ", output_prefix(None, Some(OsStr::new("0"))));
+        assert_eq!("> ", output_prefix(None, Some(OsStr::new("1"))));
+        assert_eq!(
+            "[gpu] ",
+            output_prefix(Some(OsStr::new("[gpu]")), Some(OsStr::new("1")))
+        );
+        assert_eq!("", output_prefix(Some(OsStr::new("")), None));
+    }
+
+    #[test_log::test]
+    fn env_flags() {
+        use crate::is_env_flag_set;
+        use std::ffi::OsStr;
+
+        assert!(!is_env_flag_set(None));
+        assert!(!is_env_flag_set(Some(OsStr::new(""))));
+        assert!(!is_env_flag_set(Some(OsStr::new("0"))));
+        assert!(is_env_flag_set(Some(OsStr::new("1"))));
+        assert!(is_env_flag_set(Some(OsStr::new("yes"))));
+    }
+
+    #[test_log::test]
+    fn stderr_tails() {
+        use crate::{stderr_tail, STDERR_TAIL_LINES};
+
+        assert_eq!("error: oops", stderr_tail(b"error: oops\n"));
+        assert_eq!("", stderr_tail(b""));
+
+        let long = (0..=STDERR_TAIL_LINES)
+            .map(|line| format!("line {line}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let tail = stderr_tail(long.as_bytes());
+        assert!(tail.starts_with("...\nline 1\n"));
+        assert!(tail.ends_with(&format!("line {STDERR_TAIL_LINES}")));
+    }
+
+    #[test_log::test]
+    fn teeing_stderr() {
+        use crate::{tee_stderr, STDERR_TAIL_LINES};
+
+        let mut forwarded = Vec::new();
+        assert_eq!(
+            "error: oops",
+            tee_stderr(
+                b"error: oops\n".as_slice(),
+                &mut forwarded,
+                STDERR_TAIL_LINES
+            )
+            .unwrap()
+        );
+        assert_eq!(b"error: oops\n".as_slice(), forwarded);
+
+        let mut long = (0..=STDERR_TAIL_LINES)
+            .map(|line| format!("line {line}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        long.push('\n');
+        forwarded.clear();
+        let tail = tee_stderr(long.as_bytes(), &mut forwarded, STDERR_TAIL_LINES).unwrap();
+        assert_eq!(long.as_bytes(), forwarded);
+        assert!(tail.starts_with("...\nline 1\n"));
+        assert!(tail.ends_with(&format!("line {STDERR_TAIL_LINES}")));
+    }
+
+    #[test_log::test]
+    fn cache_dir_overrides() {
+        use crate::base_cache_dir;
+        use std::ffi::OsStr;
+
+        assert_eq!(
+            std::path::PathBuf::from("/ci/cache"),
+            base_cache_dir(Some(OsStr::new("/ci/cache"))).unwrap()
+        );
+        assert!(base_cache_dir(None).unwrap().ends_with("rust-gpu"));
+        assert_eq!(
+            base_cache_dir(None).unwrap(),
+            base_cache_dir(Some(OsStr::new(""))).unwrap()
+        );
+    }
+
+    #[test_log::test]
+    fn cargo_manifest_path_from_crate_dir() {
+        let shader_crate_path = shader_crate_template_path();
+        let manifest_path = cargo_manifest_path(&shader_crate_path).unwrap();
+        assert_eq!(
+            shader_crate_path.join("Cargo.toml").canonicalize().unwrap(),
+            manifest_path
+        );
+    }
+
+    #[test_log::test]
+    fn cargo_manifest_path_from_manifest_file() {
+        let shader_crate_path = shader_crate_template_path();
+        let from_dir = cargo_manifest_path(&shader_crate_path).unwrap();
+        let from_file = cargo_manifest_path(&shader_crate_path.join("Cargo.toml")).unwrap();
+        assert_eq!(from_dir, from_file);
+    }
+
+    #[test_log::test]
+    fn cargo_manifest_path_missing() {
+        let shader_crate_path = shader_crate_template_path();
+        cargo_manifest_path(&shader_crate_path.join("src")).unwrap_err();
+        cargo_manifest_path(&shader_crate_path.join("Nope.toml")).unwrap_err();
+    }
 }
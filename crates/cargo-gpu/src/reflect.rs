@@ -0,0 +1,323 @@
+//! Minimal reflection of compiled SPIR-V modules.
+//!
+//! We only need a handful of facts about the `.spv` files that `rust-gpu` produces, such as
+//! the capabilities that they declare, so rather than depending on a full SPIR-V parser we
+//! walk the instruction stream ourselves.
+
+use anyhow::Context as _;
+
+/// The number of words in a SPIR-V module's header.
+const HEADER_WORD_COUNT: usize = 5;
+
+/// An instruction's word count is stored in the high half-word of its first word.
+pub const WORD_COUNT_SHIFT: u32 = 16;
+
+/// The major version is stored in the second-highest byte of the header's version word.
+const MAJOR_VERSION_SHIFT: u32 = 16;
+
+/// The minor version is stored in the second-lowest byte of the header's version word.
+const MINOR_VERSION_SHIFT: u32 = 8;
+
+/// A SPIR-V module read from a `.spv` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpirvModule {
+    /// All the words of the module, in native endianness.
+    words: Vec<u32>,
+}
+
+impl SpirvModule {
+    /// Read a SPIR-V module from a `.spv` file.
+    pub fn read(path: &std::path::Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("could not read SPIR-V module '{}'", path.display()))?;
+        Self::from_bytes(&bytes)
+            .with_context(|| format!("'{}' is not a valid SPIR-V module", path.display()))
+    }
+
+    /// Parse a SPIR-V module from its binary form, in either endianness.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let chunks = bytes.chunks_exact(4);
+        anyhow::ensure!(
+            chunks.remainder().is_empty(),
+            "length of {} bytes is not a multiple of 4",
+            bytes.len()
+        );
+        #[expect(
+            clippy::little_endian_bytes,
+            reason = "Big endian modules are detected by their magic number and swapped below"
+        )]
+        let mut words = chunks
+            .map(|chunk| Ok(u32::from_le_bytes(chunk.try_into()?)))
+            .collect::<anyhow::Result<Vec<u32>>>()?;
+
+        let magic = *words.first().context("module is empty")?;
+        if magic == spirv::MAGIC_NUMBER.swap_bytes() {
+            for word in &mut words {
+                *word = word.swap_bytes();
+            }
+        } else {
+            anyhow::ensure!(
+                magic == spirv::MAGIC_NUMBER,
+                "bad magic number {magic:#010x}"
+            );
+        }
+        anyhow::ensure!(
+            words.len() >= HEADER_WORD_COUNT,
+            "module is too short to contain a header"
+        );
+        Ok(Self { words })
+    }
+
+    /// The instructions of the module, as pairs of their opcode and operands. Instructions with
+    /// opcodes unknown to us are skipped.
+    fn instructions(&self) -> impl Iterator<Item = (spirv::Op, &[u32])> {
+        let mut remaining = self.words.get(HEADER_WORD_COUNT..).unwrap_or_default();
+        core::iter::from_fn(move || loop {
+            let (&first, _) = remaining.split_first()?;
+            let word_count = usize::try_from(first >> WORD_COUNT_SHIFT).ok()?.max(1);
+            let (instruction, rest) = remaining.split_at_checked(word_count)?;
+            remaining = rest;
+            let (_, operands) = instruction.split_first()?;
+            if let Some(op) = spirv::Op::from_u32(first & 0xffff) {
+                return Some((op, operands));
+            }
+        })
+    }
+
+    /// The SPIR-V version of the module, as `(major, minor)`.
+    pub fn version(&self) -> (u8, u8) {
+        let version = self.header_word(1);
+        let major = u8::try_from((version >> MAJOR_VERSION_SHIFT) & 0xff).unwrap_or_default();
+        let minor = u8::try_from((version >> MINOR_VERSION_SHIFT) & 0xff).unwrap_or_default();
+        (major, minor)
+    }
+
+    /// The generator's magic number, identifying the tool that produced the module.
+    pub fn generator(&self) -> u32 {
+        self.header_word(2)
+    }
+
+    /// The bound that all of the module's IDs are less than.
+    pub fn bound(&self) -> u32 {
+        self.header_word(3)
+    }
+
+    /// A word of the header, which is guaranteed to exist by the constructor.
+    fn header_word(&self, index: usize) -> u32 {
+        self.words.get(index).copied().unwrap_or_default()
+    }
+
+    /// The extensions declared by the module with `OpExtension`, in declaration order.
+    pub fn extensions(&self) -> Vec<String> {
+        self.instructions()
+            .filter(|(op, _)| *op == spirv::Op::Extension)
+            .map(|(_, operands)| decode_literal_string(operands))
+            .collect()
+    }
+
+    /// The local workgroup size of a compute entry point, from its `LocalSize` execution mode.
+    /// Entry points that don't have one, like vertex or fragment shaders, return `None`, as do
+    /// sizes given by ID with `LocalSizeId`.
+    pub fn workgroup_size(&self, entry_point: &str) -> Option<[u32; 3]> {
+        let id = self.instructions().find_map(|(op, operands)| {
+            let (_execution_model, rest) = operands.split_first()?;
+            let (id, name) = rest.split_first()?;
+            (op == spirv::Op::EntryPoint && decode_literal_string(name) == entry_point)
+                .then_some(*id)
+        })?;
+        self.instructions()
+            .find_map(|(op, operands)| match operands {
+                [mode_id, mode, x, y, z]
+                    if op == spirv::Op::ExecutionMode
+                        && *mode_id == id
+                        && spirv::ExecutionMode::from_u32(*mode)
+                            == Some(spirv::ExecutionMode::LocalSize) =>
+                {
+                    Some([*x, *y, *z])
+                }
+                _ => None,
+            })
+    }
+
+    /// The capabilities declared by the module with `OpCapability`, in declaration order.
+    pub fn capabilities(&self) -> Vec<spirv::Capability> {
+        self.instructions()
+            .filter(|(op, _)| *op == spirv::Op::Capability)
+            .filter_map(|(_, operands)| spirv::Capability::from_u32(*operands.first()?))
+            .collect()
+    }
+}
+
+impl core::fmt::Display for SpirvModule {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (major, minor) = self.version();
+        let capabilities = self
+            .capabilities()
+            .iter()
+            .map(|capability| format!("{capability:?}"))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "SPIR-V {major}.{minor}, generator {:#010x}, bound {}, capabilities [{}], extensions [{}]",
+            self.generator(),
+            self.bound(),
+            capabilities.join(", "),
+            self.extensions().join(", ")
+        )
+    }
+}
+
+/// Decode a SPIR-V literal string operand, which is nul-terminated UTF-8 packed into words
+/// starting from each word's lowest-order byte.
+fn decode_literal_string(operands: &[u32]) -> String {
+    let bytes = operands
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|byte| *byte != 0)
+        .collect::<Vec<u8>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Encode a string as a SPIR-V literal string operand.
+    #[expect(clippy::little_endian_bytes, reason = "Test modules are little endian")]
+    pub fn literal_string(string: &str) -> Vec<u32> {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.push(0);
+        while !bytes.chunks_exact(4).remainder().is_empty() {
+            bytes.push(0);
+        }
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    /// Encode a single instruction.
+    #[expect(clippy::as_conversions, reason = "Opcodes have no other conversion")]
+    pub fn instruction(op: spirv::Op, operands: &[u32]) -> Vec<u32> {
+        let word_count = u32::try_from(operands.len() + 1).unwrap();
+        let mut words = vec![(word_count << WORD_COUNT_SHIFT) | op as u32];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// Encode a capability operand.
+    #[expect(
+        clippy::as_conversions,
+        reason = "Capabilities have no other conversion"
+    )]
+    pub const fn capability(capability: spirv::Capability) -> u32 {
+        capability as u32
+    }
+
+    /// Build the bytes of a little endian SPIR-V module from its instructions.
+    pub fn module_bytes(instructions: &[Vec<u32>]) -> Vec<u8> {
+        let header = [spirv::MAGIC_NUMBER, 0x0001_0300, 0, 16, 0];
+        header
+            .into_iter()
+            .chain(instructions.iter().flatten().copied())
+            .flat_map(u32::to_le_bytes)
+            .collect()
+    }
+
+    #[test_log::test]
+    fn reading_capabilities() {
+        let bytes = module_bytes(&[
+            instruction(
+                spirv::Op::Capability,
+                &[capability(spirv::Capability::Shader)],
+            ),
+            instruction(
+                spirv::Op::Capability,
+                &[capability(spirv::Capability::Int8)],
+            ),
+            instruction(
+                spirv::Op::Extension,
+                &literal_string("SPV_KHR_vulkan_memory_model"),
+            ),
+            instruction(spirv::Op::MemoryModel, &[0, 1]),
+        ]);
+        let module = SpirvModule::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            vec![spirv::Capability::Shader, spirv::Capability::Int8],
+            module.capabilities()
+        );
+    }
+
+    #[test_log::test]
+    fn reading_metadata() {
+        let bytes = module_bytes(&[
+            instruction(
+                spirv::Op::Capability,
+                &[capability(spirv::Capability::Shader)],
+            ),
+            instruction(
+                spirv::Op::Extension,
+                &literal_string("SPV_KHR_vulkan_memory_model"),
+            ),
+            instruction(spirv::Op::Extension, &literal_string("SPV_KHR_int8")),
+        ]);
+        let module = SpirvModule::from_bytes(&bytes).unwrap();
+        assert_eq!((1, 3), module.version());
+        assert_eq!(0, module.generator());
+        assert_eq!(16, module.bound());
+        assert_eq!(
+            vec!["SPV_KHR_vulkan_memory_model", "SPV_KHR_int8"],
+            module.extensions()
+        );
+        assert_eq!(
+            "SPIR-V 1.3, generator 0x00000000, bound 16, capabilities [Shader], \
+             extensions [SPV_KHR_vulkan_memory_model, SPV_KHR_int8]",
+            module.to_string()
+        );
+    }
+
+    #[test_log::test]
+    fn reading_workgroup_sizes() {
+        let entry_point = |model: u32, id: u32, name: &str| {
+            let mut operands = vec![model, id];
+            operands.extend(literal_string(name));
+            instruction(spirv::Op::EntryPoint, &operands)
+        };
+        let (gl_compute, fragment, local_size, origin_upper_left) = (5, 4, 17, 7);
+        let bytes = module_bytes(&[
+            entry_point(gl_compute, 1, "main_cs"),
+            entry_point(fragment, 2, "main_fs"),
+            instruction(spirv::Op::ExecutionMode, &[1, local_size, 8, 4, 1]),
+            instruction(spirv::Op::ExecutionMode, &[2, origin_upper_left]),
+        ]);
+        let module = SpirvModule::from_bytes(&bytes).unwrap();
+        assert_eq!(Some([8, 4, 1]), module.workgroup_size("main_cs"));
+        assert_eq!(None, module.workgroup_size("main_fs"));
+        assert_eq!(None, module.workgroup_size("missing"));
+    }
+
+    #[test_log::test]
+    fn reading_big_endian_modules() {
+        let little_endian = module_bytes(&[instruction(
+            spirv::Op::Capability,
+            &[capability(spirv::Capability::Shader)],
+        )]);
+        let big_endian: Vec<u8> = little_endian
+            .chunks_exact(4)
+            .flat_map(|chunk| chunk.iter().rev().copied())
+            .collect();
+        assert_eq!(
+            SpirvModule::from_bytes(&little_endian).unwrap(),
+            SpirvModule::from_bytes(&big_endian).unwrap()
+        );
+    }
+
+    #[test_log::test]
+    fn rejecting_invalid_modules() {
+        SpirvModule::from_bytes(&[1, 2, 3]).unwrap_err();
+        SpirvModule::from_bytes(&[0; 20]).unwrap_err();
+        let mut only_magic = module_bytes(&[]);
+        only_magic.truncate(4);
+        SpirvModule::from_bytes(&only_magic).unwrap_err();
+    }
+}
@@ -1,6 +1,21 @@
 //! Display various information about `cargo gpu`, eg its cache directory.
 
-use crate::cache_dir;
+use anyhow::Context as _;
+
+use crate::{
+    cache_dir,
+    install::shell_quote,
+    spirv_cli::{SpirvCli, SpirvCliOptions},
+    target_spec_dir,
+};
+
+/// Show the cache directory.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct CacheDir {
+    /// Create the cache directory, and its `target-specs` subdirectory, if they don't exist yet.
+    #[clap(long)]
+    pub ensure: bool,
+}
 
 /// Show the computed source of the spirv-std dependency.
 #[derive(Clone, Debug, clap::Parser)]
@@ -10,13 +25,171 @@ pub struct SpirvSourceDep {
     pub shader_crate: std::path::PathBuf,
 }
 
+/// Show the environment needed to build the shader crate by hand.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct BuildEnv {
+    /// The location of the shader-crate to resolve the build environment for.
+    #[clap(long, default_value = "./")]
+    pub shader_crate: std::path::PathBuf,
+
+    /// Shader target.
+    #[clap(long, default_value = "spirv-unknown-vulkan1.2")]
+    pub shader_target: String,
+}
+
+/// Show the installed toolchains and the one required by the shader crate.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct Toolchains {
+    /// The location of the shader-crate whose required toolchain should be highlighted.
+    #[clap(long, default_value = "./")]
+    pub shader_crate: std::path::PathBuf,
+}
+
+/// The graphics API that a target is for.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TargetApi {
+    /// Every target.
+    #[default]
+    All,
+    /// The `spirv-unknown-vulkan*` targets.
+    Vulkan,
+    /// The `spirv-unknown-opengl*` targets.
+    Opengl,
+    /// The `spirv-unknown-spv*` targets, for plain SPIR-V versions without an API.
+    Spv,
+}
+
+impl TargetApi {
+    /// Whether the target is for this API.
+    fn matches(self, target: &str) -> bool {
+        let api = target.trim_start_matches("spirv-unknown-");
+        match self {
+            Self::All => true,
+            Self::Vulkan => api.starts_with("vulkan"),
+            Self::Opengl => api.starts_with("opengl"),
+            Self::Spv => api.starts_with("spv"),
+        }
+    }
+}
+
+/// Show the targets that shaders can be compiled for.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct Targets {
+    /// Only show the targets for this API.
+    #[clap(long, value_enum, default_value_t)]
+    pub api: TargetApi,
+}
+
+/// Show the target spec of a target.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct TargetSpec {
+    /// The target whose spec to show, eg "spirv-unknown-vulkan1.2".
+    pub target: String,
+}
+
+/// Show the entry points of the shader crate.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct EntryPoints {
+    /// How to compile the shader crate. Its modules are written to a scratch directory in the
+    /// cache, so `--output-dir` has no effect.
+    #[clap(flatten)]
+    pub build: crate::build::Build,
+}
+
+/// An installation of `spirv-builder-cli` in the cache directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CachedInstall {
+    /// The directory of the installation, inside the host's installations directory.
+    path: std::path::PathBuf,
+    /// The `rust-gpu` source that the installation was made for, from the installation's
+    /// `source.json`. Older installations don't have one, and their directory name is sanitised
+    /// from the source with `SpirvSource::cache_key`, which can't be reversed, so for them this
+    /// is left in its sanitised form.
+    source: String,
+    /// The toolchain channel that the installation was built with, if the directory name has one.
+    channel: Option<String>,
+    /// The size in bytes of the `rustc_codegen_spirv` dylib, if it has been built.
+    dylib_size: Option<u64>,
+}
+
+impl CachedInstall {
+    /// Read all the installations in `dir`, sorted by their directory name.
+    fn read_all(dir: &std::path::Path) -> anyhow::Result<Vec<Self>> {
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut installs = std::fs::read_dir(dir)
+            .with_context(|| format!("could not read directory '{}'", dir.display()))?
+            .map(|maybe_entry| Ok(maybe_entry?.path()))
+            .collect::<anyhow::Result<Vec<std::path::PathBuf>>>()?
+            .into_iter()
+            .filter(|path| path.is_dir())
+            .map(Self::read)
+            .collect::<Vec<_>>();
+        installs.sort_by(|left, right| left.path.cmp(&right.path));
+        Ok(installs)
+    }
+
+    /// Read the installation in the directory at `path`.
+    fn read(path: std::path::PathBuf) -> Self {
+        let dirname = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // The directory is named after `SpirvCli`'s `source+channel` form.
+        let (sanitised_source, channel) = dirname.rsplit_once('+').map_or_else(
+            || (dirname.clone(), None),
+            |(source, channel)| (source.to_owned(), Some(channel.to_owned())),
+        );
+        let source = std::fs::read_to_string(path.join(crate::install::SOURCE_FILENAME))
+            .ok()
+            .and_then(|json| serde_json::from_str::<crate::spirv_source::SpirvSource>(&json).ok())
+            .map_or(sanitised_source, |source| source.to_string());
+        let dylib_size = std::fs::metadata(path.join(crate::install::dylib_filename()))
+            .ok()
+            .map(|metadata| metadata.len());
+        Self {
+            path,
+            source,
+            channel,
+            dylib_size,
+        }
+    }
+}
+
 /// Different tidbits of information that can be queried at the command line.
 #[derive(Clone, Debug, clap::Subcommand)]
 pub enum Info {
     /// Displays the location of the cache directory
-    CacheDirectory,
+    CacheDirectory(CacheDir),
     /// The source location of spirv-std
     SpirvSource(SpirvSourceDep),
+    /// The environment variables and paths needed to reproduce a shader build by hand with
+    /// `cargo +<channel> build`. Nothing is installed or built.
+    Env(BuildEnv),
+    /// The toolchain channel that the shader crate needs, eg "nightly-2024-04-24". A crates.io
+    /// release of `rust-gpu` is looked up in cargo's registry, while other sources read their
+    /// repo, which is cloned into the cache if it isn't already there, but nothing is installed
+    Toolchain(SpirvSourceDep),
+    /// The toolchains installed with `rustup`, highlighting the one that the shader crate
+    /// requires, and which of its required components are installed
+    Toolchains(Toolchains),
+    /// The names of the shader crate's entry points, one per line. `rust-gpu` can only find
+    /// them by compiling the shader crate, so this runs a build
+    EntryPoints(Box<EntryPoints>),
+    /// The `spirv-builder-cli` installations in the cache directory for this host, with the
+    /// source and toolchain they were built for and whether their `rustc_codegen_spirv` dylib
+    /// exists
+    Installs,
+    /// The targets that shaders can be compiled for with `--shader-target`, one per line,
+    /// grouped by API
+    Targets(Targets),
+    /// The JSON target spec that shaders are compiled with for a target, from the cache
+    /// directory. When it isn't in the cache yet, the copy bundled with `cargo gpu` is shown
+    TargetSpec(TargetSpec),
+    /// The short names that `--spirv-builder-source-alias` accepts for well-known `rust-gpu`
+    /// repos, and the URLs they stand for
+    SourceAliases,
 }
 
 /// `cargo gpu show`
@@ -28,6 +201,15 @@ pub struct Show {
 }
 
 impl Show {
+    /// The install flags of the queried information, if it resolves `rust-gpu` with them.
+    pub fn install(&self) -> Option<&crate::install::Install> {
+        if let Info::EntryPoints(entry_points) = &self.command {
+            Some(&entry_points.build.install)
+        } else {
+            None
+        }
+    }
+
     /// Entrypoint
     pub fn run(self) -> anyhow::Result<()> {
         log::info!("{:?}: ", self.command);
@@ -38,18 +220,388 @@ impl Show {
                       so we _don't_ want to use `crate::user_output`, as that prefixes a crab."
         )]
         match self.command {
-            Info::CacheDirectory => {
-                println!("{}\n", cache_dir()?.display());
+            Info::CacheDirectory(CacheDir { ensure }) => {
+                let dir = cache_dir()?;
+                if ensure {
+                    std::fs::create_dir_all(&dir).with_context(|| {
+                        format!("could not create cache directory '{}'", dir.display())
+                    })?;
+                    target_spec_dir()?;
+                }
+                println!("{}\n", dir.display());
             }
             Info::SpirvSource(SpirvSourceDep { shader_crate }) => {
                 let rust_gpu_source =
                     crate::spirv_source::SpirvSource::get_spirv_std_dep_definition(&shader_crate)?;
-                {
-                    println!("{rust_gpu_source}\n");
+                println!("{rust_gpu_source}\n");
+                if let crate::spirv_source::SpirvSource::Path((path, version)) = &rust_gpu_source {
+                    Self::print_local_source(std::path::Path::new(path), version);
                 }
             }
+            Info::Env(build_env) => Self::print_build_env(&build_env)?,
+            Info::Toolchain(SpirvSourceDep { shader_crate }) => {
+                let spirv_cli = SpirvCli::new(&shader_crate, SpirvCliOptions::default())?;
+                println!("{}", spirv_cli.channel);
+            }
+            Info::Toolchains(toolchains) => Self::print_toolchains(&toolchains)?,
+            Info::EntryPoints(entry_points) => {
+                for entry_point in entry_points.build.entry_points()? {
+                    println!("{entry_point}");
+                }
+            }
+            Info::Installs => Self::print_installs()?,
+            Info::Targets(Targets { api }) => {
+                for target in crate::install::target_names().filter(|target| api.matches(target)) {
+                    println!("{target}");
+                }
+            }
+            Info::SourceAliases => {
+                let rows = crate::spirv_source::SOURCE_ALIASES
+                    .iter()
+                    .map(|(alias, url)| ((*alias).to_owned(), (*url).to_owned()))
+                    .collect::<Vec<_>>();
+                println!("{}", format_table(("ALIAS", "URL"), &rows));
+            }
+            Info::TargetSpec(TargetSpec { target }) => {
+                println!(
+                    "{}",
+                    read_target_spec(&target, &crate::target_spec_path()?)?
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Print the parts of a local `rust-gpu` source separately, to help debug its resolution.
+    #[expect(
+        clippy::print_stdout,
+        reason = "The output of this command could potentially be used in a script"
+    )]
+    fn print_local_source(spirv_std_path: &std::path::Path, version: &str) {
+        println!("version: {}", version.trim_start_matches('v'));
+        println!("spirv-std: {}", spirv_std_path.display());
+        if let Some(root) = crate::spirv_source::SpirvSource::find_local_repo_root(spirv_std_path) {
+            println!("repo root: {}", root.display());
+        } else {
+            log::warn!(
+                "'{}' is not inside a recognizable `rust-gpu` repo",
+                spirv_std_path.display()
+            );
+            println!("repo root: not found, expected an ancestor with `crates/spirv-builder`");
+        }
+    }
+
+    /// Print the cached installations, separated by blank lines.
+    #[expect(
+        clippy::print_stdout,
+        reason = "The output of this command could potentially be used in a script"
+    )]
+    fn print_installs() -> anyhow::Result<()> {
+        for install in
+            CachedInstall::read_all(&crate::spirv_cli::installs_dir(&crate::spirv_cli::host())?)?
+        {
+            println!("{}", install.path.display());
+            println!("source: {}", install.source);
+            println!(
+                "channel: {}",
+                install.channel.as_deref().unwrap_or("unknown")
+            );
+            match install.dylib_size {
+                Some(size) => println!("dylib: {size} bytes"),
+                None => println!("dylib: missing"),
+            }
+            println!();
+        }
+        Ok(())
+    }
+
+    /// Print the build environment as shell-quoted `KEY=value` lines that can be `source`d by a
+    /// shell.
+    #[expect(
+        clippy::print_stdout,
+        reason = "The output of this command is intended to be used in a script"
+    )]
+    fn print_build_env(build_env: &BuildEnv) -> anyhow::Result<()> {
+        let spirv_cli = SpirvCli::new(&build_env.shader_crate, SpirvCliOptions::default())?;
+        let checkout = spirv_cli.cached_checkout_path()?;
+        let target_spec_path = target_spec_dir()?.join(format!("{}.json", build_env.shader_target));
+
+        for line in Self::build_env_lines(&spirv_cli, &checkout, &target_spec_path) {
+            println!("{line}");
+        }
+
+        Ok(())
+    }
+
+    /// The lines printed by `cargo gpu show env`.
+    ///
+    /// The backend's directory is prepended to the dynamic library search path, rather than
+    /// replacing it, so that sourcing the lines doesn't break the libraries that other programs
+    /// need.
+    fn build_env_lines(
+        spirv_cli: &SpirvCli,
+        checkout: &std::path::Path,
+        target_spec_path: &std::path::Path,
+    ) -> Vec<String> {
+        let (dylib_path_envvar, separator) = if cfg!(windows) {
+            ("PATH", ';')
+        } else if cfg!(target_os = "macos") {
+            ("DYLD_FALLBACK_LIBRARY_PATH", ':')
+        } else {
+            ("LD_LIBRARY_PATH", ':')
+        };
+        let quote = |value: &dyn AsRef<std::ffi::OsStr>| shell_quote(value.as_ref());
+
+        vec![
+            format!("export SPIRV_SOURCE={}", quote(&spirv_cli.source.to_string())),
+            format!("export RUSTUP_TOOLCHAIN={}", quote(&spirv_cli.channel)),
+            format!(
+                "export RUSTC_CODEGEN_SPIRV={}",
+                quote(&checkout.join(crate::install::dylib_filename()))
+            ),
+            format!(
+                "export {dylib_path_envvar}={}\"${{{dylib_path_envvar}:+{separator}${dylib_path_envvar}}}\"",
+                quote(&checkout)
+            ),
+            format!(
+                "export SPIRV_BUILDER_CLI={}",
+                quote(&checkout.join("spirv-builder-cli"))
+            ),
+            format!("export TARGET_SPEC={}", quote(&target_spec_path)),
+        ]
+    }
+
+    /// Print tables of the installed toolchains and of the required components.
+    #[expect(
+        clippy::print_stdout,
+        reason = "The output of this command could potentially be used in a script"
+    )]
+    fn print_toolchains(toolchains: &Toolchains) -> anyhow::Result<()> {
+        let spirv_cli = SpirvCli::new(&toolchains.shader_crate, SpirvCliOptions::default())?;
+        let is_required_installed = spirv_cli.is_toolchain_installed()?;
+
+        let mut toolchain_rows: Vec<(String, String)> = spirv_cli
+            .installed_toolchains()?
+            .into_iter()
+            .map(|toolchain| {
+                let status = if toolchain.starts_with(&spirv_cli.channel) {
+                    "required"
+                } else {
+                    ""
+                };
+                (toolchain, status.to_owned())
+            })
+            .collect();
+        if !is_required_installed {
+            toolchain_rows.push((spirv_cli.channel.clone(), "required, missing".to_owned()));
         }
+        println!("{}", format_table(("TOOLCHAIN", "STATUS"), &toolchain_rows));
+
+        let component_rows: Vec<(String, String)> = if is_required_installed {
+            spirv_cli
+                .required_components_status()?
+                .into_iter()
+                .map(|(component, is_installed)| {
+                    let status = if is_installed { "installed" } else { "missing" };
+                    (component.to_owned(), status.to_owned())
+                })
+                .collect()
+        } else {
+            crate::spirv_cli::REQUIRED_COMPONENTS
+                .iter()
+                .map(|component| ((*component).to_owned(), "missing".to_owned()))
+                .collect()
+        };
+        println!("{}", format_table(("COMPONENT", "STATUS"), &component_rows));
 
         Ok(())
     }
 }
+
+/// Format rows of two columns as a plain text table, with the first column padded to align
+/// the second.
+fn format_table(header: (&str, &str), rows: &[(String, String)]) -> String {
+    let width = rows
+        .iter()
+        .map(|(first, _)| first.len())
+        .chain(core::iter::once(header.0.len()))
+        .max()
+        .unwrap_or_default();
+    core::iter::once((header.0, header.1))
+        .chain(
+            rows.iter()
+                .map(|(first, second)| (first.as_str(), second.as_str())),
+        )
+        .map(|(first, second)| format!("{first:<width$}  {second}").trim_end().to_owned())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Read the target spec of `target` from `spec_dir`, or the bundled copy of it if it isn't
+/// there yet.
+fn read_target_spec(target: &str, spec_dir: &std::path::Path) -> anyhow::Result<String> {
+    let path = spec_dir.join(format!("{target}.json"));
+    if path.is_file() {
+        return std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read target spec '{}'", path.display()));
+    }
+    let bundled = crate::install::bundled_target_spec(target).with_context(|| {
+        format!(
+            "there is no target spec for '{target}' at '{}', and `cargo gpu` doesn't bundle one. \
+             See `cargo gpu show targets` for the targets it does bundle",
+            path.display()
+        )
+    })?;
+    // Logged rather than printed, so that stdout is only the JSON.
+    log::warn!(
+        "'{}' doesn't exist yet, so this is the copy bundled with `cargo gpu`, which is written \
+         there when installing",
+        path.display()
+    );
+    Ok(bundled.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn rendering_build_env() {
+        let lines = Show::build_env_lines(
+            &SpirvCli::for_tests(),
+            std::path::Path::new("/cache/it's installed"),
+            std::path::Path::new("/cache/target-specs/spirv-unknown-vulkan1.2.json"),
+        );
+        let dylib = format!(
+            "'/cache/it'\\''s installed/{}'",
+            crate::install::dylib_filename()
+        );
+        let dylib_path_line = if cfg!(windows) {
+            "export PATH='/cache/it'\\''s installed'\"${PATH:+;$PATH}\""
+        } else if cfg!(target_os = "macos") {
+            "export DYLD_FALLBACK_LIBRARY_PATH='/cache/it'\\''s installed'\
+             \"${DYLD_FALLBACK_LIBRARY_PATH:+:$DYLD_FALLBACK_LIBRARY_PATH}\""
+        } else {
+            "export LD_LIBRARY_PATH='/cache/it'\\''s installed'\"${LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}\""
+        };
+        assert_eq!(
+            vec![
+                "export SPIRV_SOURCE=0.9.0".to_owned(),
+                "export RUSTUP_TOOLCHAIN=nightly-2024-04-24".to_owned(),
+                format!("export RUSTC_CODEGEN_SPIRV={dylib}"),
+                dylib_path_line.to_owned(),
+                "export SPIRV_BUILDER_CLI='/cache/it'\\''s installed/spirv-builder-cli'".to_owned(),
+                "export TARGET_SPEC=/cache/target-specs/spirv-unknown-vulkan1.2.json".to_owned(),
+            ],
+            lines
+        );
+    }
+
+    #[test_log::test]
+    fn formatting_tables() {
+        let rows = [
+            ("nightly-2024-04-24".to_owned(), "required".to_owned()),
+            ("stable".to_owned(), String::new()),
+        ];
+        assert_eq!(
+            "TOOLCHAIN           STATUS\n\
+             nightly-2024-04-24  required\n\
+             stable",
+            format_table(("TOOLCHAIN", "STATUS"), &rows)
+        );
+    }
+
+    #[test_log::test]
+    fn filtering_targets_by_api() {
+        let targets = |api: TargetApi| {
+            crate::install::target_names()
+                .filter(|target| api.matches(target))
+                .collect::<Vec<_>>()
+        };
+        assert!(targets(TargetApi::Opengl).contains(&"spirv-unknown-opengl4.5"));
+        assert!(targets(TargetApi::Vulkan).contains(&"spirv-unknown-vulkan1.1spv1.4"));
+        assert!(targets(TargetApi::Spv).contains(&"spirv-unknown-spv1.3"));
+        assert_eq!(
+            targets(TargetApi::All).len(),
+            targets(TargetApi::Vulkan).len()
+                + targets(TargetApi::Opengl).len()
+                + targets(TargetApi::Spv).len()
+        );
+    }
+
+    #[test_log::test]
+    fn reading_target_specs() {
+        let dir = crate::test::fixture_dir("show-target-spec");
+        std::fs::write(
+            dir.join("spirv-unknown-vulkan1.2.json"),
+            "{\"cached\": true}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            "{\"cached\": true}",
+            read_target_spec("spirv-unknown-vulkan1.2", &dir).unwrap()
+        );
+        assert_eq!(
+            crate::install::bundled_target_spec("spirv-unknown-vulkan1.1"),
+            Some(
+                read_target_spec("spirv-unknown-vulkan1.1", &dir)
+                    .unwrap()
+                    .as_str()
+            )
+        );
+        read_target_spec("spirv-unknown-vulkan9", &dir).unwrap_err();
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn reading_cached_installs() {
+        let dir = crate::test::fixture_dir("cached-installs");
+        let built = dir.join("v0_9_0+nightly-2024-04-24");
+        std::fs::create_dir_all(&built).unwrap();
+        std::fs::write(built.join(crate::install::dylib_filename()), [0; 3]).unwrap();
+        let unbuilt = dir.join("partial");
+        std::fs::create_dir_all(&unbuilt).unwrap();
+        let git = dir.join("https___github_com_Rust-GPU_rust-gpu+82a0f69-ddd241e7d4ee52b8+nightly");
+        std::fs::create_dir_all(&git).unwrap();
+        std::fs::write(
+            git.join(crate::install::SOURCE_FILENAME),
+            serde_json::to_string(&crate::spirv_source::SpirvSource::Git {
+                url: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+                rev: "82a0f69".to_owned(),
+            })
+            .unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("stray-file"), "").unwrap();
+
+        assert_eq!(
+            vec![
+                CachedInstall {
+                    path: git,
+                    source: "https://github.com/Rust-GPU/rust-gpu+82a0f69".to_owned(),
+                    channel: Some("nightly".to_owned()),
+                    dylib_size: None,
+                },
+                CachedInstall {
+                    path: unbuilt,
+                    source: "partial".to_owned(),
+                    channel: None,
+                    dylib_size: None,
+                },
+                CachedInstall {
+                    path: built,
+                    source: "v0_9_0".to_owned(),
+                    channel: Some("nightly-2024-04-24".to_owned()),
+                    dylib_size: Some(3),
+                },
+            ],
+            CachedInstall::read_all(&dir).unwrap()
+        );
+        assert!(CachedInstall::read_all(&dir.join("missing"))
+            .unwrap()
+            .is_empty());
+        crate::test::tests_teardown();
+    }
+}
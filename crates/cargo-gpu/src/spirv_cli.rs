@@ -5,6 +5,82 @@ use anyhow::Context as _;
 
 use crate::spirv_source::SpirvSource;
 
+/// The toolchain components that `rust-gpu` needs.
+pub const REQUIRED_COMPONENTS: [&str; 3] = ["rust-src", "rustc-dev", "llvm-tools"];
+
+/// The `rustup` program that toolchains are managed with, unless a [`SpirvCli`] is given
+/// another, eg a fake in tests.
+pub const RUSTUP: &str = "rustup";
+
+/// Explain a failure to run `rustup`. Its absence, eg with a distribution's packaged Rust, is
+/// otherwise reported as an opaque "No such file or directory".
+fn rustup_error(error: std::io::Error) -> anyhow::Error {
+    if error.kind() == std::io::ErrorKind::NotFound {
+        anyhow::anyhow!(
+            "`rustup` is not installed: `rust-gpu` needs a specific nightly toolchain managed \
+             by `rustup`, which can be installed from https://rustup.rs. Alternatively, give the \
+             location of an existing toolchain with `--toolchain-bin-dir`"
+        )
+    } else {
+        anyhow::Error::new(error).context("could not run `rustup`")
+    }
+}
+
+/// The names of the toolchains installed with `rustup`, eg
+/// "nightly-2024-04-24-x86_64-unknown-linux-gnu".
+fn installed_toolchains(rustup: &std::path::Path) -> anyhow::Result<Vec<String>> {
+    let output_toolchain_list = std::process::Command::new(rustup)
+        .args(["toolchain", "list"])
+        .output()
+        .map_err(rustup_error)?;
+    anyhow::ensure!(
+        output_toolchain_list.status.success(),
+        "could not list installed toolchains:\n{}",
+        crate::stderr_tail(&output_toolchain_list.stderr)
+    );
+    Ok(String::from_utf8_lossy(&output_toolchain_list.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// The host that `cargo gpu` runs on, eg "x86_64-linux-gnu". The C library or ABI is part of
+/// it, as eg the dylibs of `x86_64-linux-gnu` and `x86_64-linux-musl` can't load each other's.
+pub fn host() -> String {
+    host_from(std::env::consts::ARCH, std::env::consts::OS, HOST_ENV)
+}
+
+/// The target environment, the `target_env` of the host's target triple, eg "gnu", "musl" or
+/// "msvc", or empty for hosts that don't distinguish them, like macOS.
+const HOST_ENV: &str = if cfg!(target_env = "gnu") {
+    "gnu"
+} else if cfg!(target_env = "musl") {
+    "musl"
+} else if cfg!(target_env = "msvc") {
+    "msvc"
+} else {
+    ""
+};
+
+/// The name of a host with the architecture `arch`, OS `os` and target environment `env`.
+fn host_from(arch: &str, os: &str, env: &str) -> String {
+    if env.is_empty() {
+        format!("{arch}-{os}")
+    } else {
+        format!("{arch}-{os}-{env}")
+    }
+}
+
+/// The directory of the installations for a host, inside the cache directory.
+///
+/// A `rustc_codegen_spirv` dylib built on one host can't be loaded on another, so when the
+/// cache directory is shared, eg on a network drive or a container volume, each host needs its
+/// own installations.
+pub fn installs_dir(host: &str) -> anyhow::Result<std::path::PathBuf> {
+    Ok(crate::cache_dir()?.join("spirv-builder-cli").join(host))
+}
+
 /// Cargo dependency for `spirv-builder` and the rust toolchain channel.
 #[derive(Debug, Clone)]
 pub struct SpirvCli {
@@ -25,6 +101,31 @@ pub struct SpirvCli {
     pub date: chrono::NaiveDate,
     /// Has the user overridden the toolchain consent prompt
     is_toolchain_install_consent: bool,
+    /// Directory with the toolchain's `cargo` and `rustc`, to use instead of `rustup`'s proxies
+    pub toolchain_bin_dir: Option<std::path::PathBuf>,
+    /// The `rustup` program to query and install toolchains with, which is [`RUSTUP`] except
+    /// in tests, where it's a fake that records how it's run.
+    rustup: std::path::PathBuf,
+}
+
+/// How [`SpirvCli::new`] resolves `rust-gpu` and its toolchain. By default everything is
+/// resolved from the shader crate's `spirv-std` dependency.
+#[derive(Debug, Clone, Default)]
+pub struct SpirvCliOptions {
+    /// Git repo of `rust-gpu` to use, with `rust_gpu_version` as its revision
+    pub rust_gpu_source: Option<String>,
+    /// Version of `rust-gpu` on crates.io to use, or the revision of `rust_gpu_source`
+    pub rust_gpu_version: Option<String>,
+    /// Toolchain channel to use instead of the one that `rust-gpu` requires
+    pub rust_gpu_channel: Option<String>,
+    /// Has the user overridden the toolchain consent prompt
+    pub is_toolchain_install_consent: bool,
+    /// Directory with the toolchain's `cargo` and `rustc`, to use instead of `rustup`'s proxies
+    pub toolchain_bin_dir: Option<std::path::PathBuf>,
+    /// Source of `spirv-std` to use instead of the shader crate's dependency
+    pub spirv_std_source: Option<SpirvSource>,
+    /// Query crates.io for the toolchain channel of a release of `rust-gpu`, even if it's cached
+    pub is_channel_requeried: bool,
 }
 
 impl core::fmt::Display for SpirvCli {
@@ -40,14 +141,28 @@ impl core::fmt::Display for SpirvCli {
 impl SpirvCli {
     /// Create instance
     pub fn new(
-        shader_crate_path: &std::path::PathBuf,
-        maybe_rust_gpu_source: Option<String>,
-        maybe_rust_gpu_version: Option<String>,
-        maybe_rust_gpu_channel: Option<String>,
-        is_toolchain_install_consent: bool,
+        shader_crate_path: &std::path::Path,
+        options: SpirvCliOptions,
     ) -> anyhow::Result<Self> {
-        let (default_rust_gpu_source, rust_gpu_date, default_rust_gpu_channel) =
-            SpirvSource::get_rust_gpu_deps_from_shader(shader_crate_path)?;
+        let SpirvCliOptions {
+            rust_gpu_source: maybe_rust_gpu_source,
+            rust_gpu_version: maybe_rust_gpu_version,
+            rust_gpu_channel: maybe_rust_gpu_channel,
+            is_toolchain_install_consent,
+            toolchain_bin_dir,
+            spirv_std_source: maybe_spirv_std_source,
+            is_channel_requeried,
+        } = options;
+        let (default_rust_gpu_source, rust_gpu_date, rust_gpu_channel) =
+            if let Some(spirv_std_source) = maybe_spirv_std_source {
+                spirv_std_source.get_rust_gpu_deps(maybe_rust_gpu_channel, is_channel_requeried)?
+            } else {
+                SpirvSource::get_rust_gpu_deps_from_shader(
+                    shader_crate_path,
+                    maybe_rust_gpu_channel,
+                    is_channel_requeried,
+                )?
+            };
 
         let mut maybe_spirv_source: Option<SpirvSource> = None;
         if let Some(rust_gpu_version) = maybe_rust_gpu_version {
@@ -63,17 +178,27 @@ impl SpirvCli {
 
         Ok(Self {
             source: maybe_spirv_source.unwrap_or(default_rust_gpu_source),
-            channel: maybe_rust_gpu_channel.unwrap_or(default_rust_gpu_channel),
+            channel: rust_gpu_channel,
             date: rust_gpu_date,
             is_toolchain_install_consent,
+            toolchain_bin_dir,
+            rustup: RUSTUP.into(),
         })
     }
 
+    /// The cache directory of the installation for this source and toolchain, which may not
+    /// exist yet.
+    pub fn checkout_path(&self) -> anyhow::Result<std::path::PathBuf> {
+        Ok(installs_dir(&host())?.join(crate::to_dirname(&format!(
+            "{}+{}",
+            self.source.cache_key(),
+            self.channel
+        ))))
+    }
+
     /// Create and/or return the cache directory
     pub fn cached_checkout_path(&self) -> anyhow::Result<std::path::PathBuf> {
-        let checkout_dir = crate::cache_dir()?
-            .join("spirv-builder-cli")
-            .join(crate::to_dirname(self.to_string().as_ref()));
+        let checkout_dir = self.checkout_path()?;
         std::fs::create_dir_all(&checkout_dir).with_context(|| {
             format!("could not create checkout dir '{}'", checkout_dir.display())
         })?;
@@ -81,77 +206,109 @@ impl SpirvCli {
         Ok(checkout_dir)
     }
 
+    /// A command running one of the toolchain's tools, eg `cargo`. Without a toolchain bin
+    /// directory this relies on the `rustup` proxy to pick the toolchain from `+channel`.
+    ///
+    /// With one, `RUSTC` and `PATH` point at the directory too, so that the tools that `cargo`
+    /// runs itself, like `rustc` and build scripts calling `cargo`, are from the same toolchain.
+    pub fn toolchain_command(&self, tool: &str) -> anyhow::Result<std::process::Command> {
+        let Some(dir) = &self.toolchain_bin_dir else {
+            let mut command = std::process::Command::new(tool);
+            command.arg(format!("+{}", self.channel));
+            return Ok(command);
+        };
+        let mut command = std::process::Command::new(toolchain_bin_path(dir, tool));
+        command
+            .env("RUSTC", toolchain_bin_path(dir, "rustc"))
+            .env("PATH", path_with_toolchain_bin_dir(dir)?);
+        Ok(command)
+    }
+
+    /// The verbose version info of the toolchain's `rustc`, as reported by `rustc -vV`.
+    pub fn rustc_version(&self) -> anyhow::Result<String> {
+        let output_rustc_version = self.toolchain_command("rustc")?.arg("-vV").output()?;
+        anyhow::ensure!(
+            output_rustc_version.status.success(),
+            "could not get the `rustc` version of toolchain {}:\n{}",
+            self.channel,
+            crate::stderr_tail(&output_rustc_version.stderr)
+        );
+        Ok(String::from_utf8_lossy(&output_rustc_version.stdout).into_owned())
+    }
+
     /// Use `rustup` to install the toolchain and components, if not already installed.
     ///
     /// Pretty much runs:
     ///
     /// * rustup toolchain add nightly-2024-04-24
     /// * rustup component add --toolchain nightly-2024-04-24 rust-src rustc-dev llvm-tools
+    ///
+    /// A toolchain that isn't managed by `rustup` can only be checked for its binaries, so its
+    /// components are assumed to be installed.
     pub fn ensure_toolchain_and_components_exist(&self) -> anyhow::Result<()> {
-        // Check for the required toolchain
-        let output_toolchain_list = std::process::Command::new("rustup")
-            .args(["toolchain", "list"])
-            .output()?;
-        anyhow::ensure!(
-            output_toolchain_list.status.success(),
-            "could not list installed toolchains"
-        );
-        let string_toolchain_list = String::from_utf8_lossy(&output_toolchain_list.stdout);
-        if string_toolchain_list
-            .split_whitespace()
-            .any(|toolchain| toolchain.starts_with(&self.channel))
-        {
+        if let Some(dir) = &self.toolchain_bin_dir {
+            for tool in ["cargo", "rustc"] {
+                let path = toolchain_bin_path(dir, tool);
+                anyhow::ensure!(
+                    path.is_file(),
+                    "could not find `{tool}` in toolchain bin directory '{}'",
+                    dir.display()
+                );
+            }
+            log::debug!(
+                "using the toolchain in '{}' without `rustup`",
+                dir.display()
+            );
+            return Ok(());
+        }
+
+        if self.is_toolchain_installed()? {
             log::debug!("toolchain {} is already installed", self.channel);
         } else {
+            anyhow::ensure!(
+                !crate::is_offline(),
+                "toolchain {} is not installed, and `--offline` prevents installing it",
+                self.channel
+            );
             self.get_consent_for_toolchain_install(
                 format!("Install Rust {} with `rustup`", self.channel).as_ref(),
             )?;
 
-            let output_toolchain_add = std::process::Command::new("rustup")
+            let output_toolchain_add = std::process::Command::new(&self.rustup)
                 .args(["toolchain", "add"])
                 .arg(&self.channel)
                 .stdout(std::process::Stdio::inherit())
                 .stderr(std::process::Stdio::inherit())
-                .output()?;
+                .output()
+                .map_err(rustup_error)?;
             anyhow::ensure!(
                 output_toolchain_add.status.success(),
                 "could not install required toolchain"
             );
         }
 
-        // Check for the required components
-        let output_component_list = std::process::Command::new("rustup")
-            .args(["component", "list", "--toolchain"])
-            .arg(&self.channel)
-            .output()?;
-        anyhow::ensure!(
-            output_component_list.status.success(),
-            "could not list installed components"
-        );
-        let string_component_list = String::from_utf8_lossy(&output_component_list.stdout);
-        let required_components = ["rust-src", "rustc-dev", "llvm-tools"];
-        let installed_components = string_component_list.lines().collect::<Vec<_>>();
-        let all_components_installed = required_components.iter().all(|component| {
-            installed_components.iter().any(|installed_component| {
-                let is_component = installed_component.starts_with(component);
-                let is_installed = installed_component.ends_with("(installed)");
-                is_component && is_installed
-            })
-        });
-        if all_components_installed {
+        if self.all_required_toolchain_components_installed()? {
             log::debug!("all required components are installed");
         } else {
+            anyhow::ensure!(
+                !crate::is_offline(),
+                "toolchain {} is missing some of the components {}, and `--offline` prevents \
+                 installing them",
+                self.channel,
+                REQUIRED_COMPONENTS.join(", ")
+            );
             self.get_consent_for_toolchain_install(
                 "Install toolchain components (rust-src, rustc-dev, llvm-tools) with `rustup`",
             )?;
 
-            let output_component_add = std::process::Command::new("rustup")
+            let output_component_add = std::process::Command::new(&self.rustup)
                 .args(["component", "add", "--toolchain"])
                 .arg(&self.channel)
-                .args(["rust-src", "rustc-dev", "llvm-tools"])
+                .args(REQUIRED_COMPONENTS)
                 .stdout(std::process::Stdio::inherit())
                 .stderr(std::process::Stdio::inherit())
-                .output()?;
+                .output()
+                .map_err(rustup_error)?;
             anyhow::ensure!(
                 output_component_add.status.success(),
                 "could not install required components"
@@ -161,23 +318,92 @@ impl SpirvCli {
         Ok(())
     }
 
+    /// The `rustup` commands that would install the toolchain and the required components that
+    /// are missing, for `--print-toolchain-install-commands`. Nothing is installed. A toolchain
+    /// from `--toolchain-bin-dir` isn't managed by `rustup`, so it never needs any.
+    pub fn toolchain_install_commands(&self) -> anyhow::Result<Vec<String>> {
+        if self.toolchain_bin_dir.is_some() {
+            return Ok(Vec::new());
+        }
+        let is_toolchain_installed = self.is_toolchain_installed()?;
+        let missing_components = if is_toolchain_installed {
+            self.required_components_status()?
+                .into_iter()
+                .filter(|(_, is_installed)| !is_installed)
+                .map(|(component, _)| component)
+                .collect()
+        } else {
+            REQUIRED_COMPONENTS.to_vec()
+        };
+        Ok(install_commands(
+            &self.channel,
+            !is_toolchain_installed,
+            &missing_components,
+        ))
+    }
+
+    /// Whether `rustup` can be run.
+    pub fn is_rustup_installed(&self) -> bool {
+        std::process::Command::new(&self.rustup)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    /// The names of all of the toolchains installed with `rustup`, not only the one needed.
+    pub fn installed_toolchains(&self) -> anyhow::Result<Vec<String>> {
+        installed_toolchains(&self.rustup)
+    }
+
+    /// Whether `rustup` has the toolchain installed.
+    pub fn is_toolchain_installed(&self) -> anyhow::Result<bool> {
+        Ok(self
+            .installed_toolchains()?
+            .iter()
+            .any(|toolchain| toolchain.starts_with(&self.channel)))
+    }
+
+    /// Each of the [`REQUIRED_COMPONENTS`] and whether `rustup` has it installed in the toolchain.
+    pub fn required_components_status(&self) -> anyhow::Result<Vec<(&'static str, bool)>> {
+        let output_component_list = std::process::Command::new(&self.rustup)
+            .args(["component", "list", "--toolchain"])
+            .arg(&self.channel)
+            .output()
+            .map_err(rustup_error)?;
+        anyhow::ensure!(
+            output_component_list.status.success(),
+            "could not list installed components:\n{}",
+            crate::stderr_tail(&output_component_list.stderr)
+        );
+        let string_component_list = String::from_utf8_lossy(&output_component_list.stdout);
+        let installed_components = string_component_list.lines().collect::<Vec<_>>();
+        Ok(REQUIRED_COMPONENTS
+            .into_iter()
+            .map(|component| {
+                let is_installed = installed_components.iter().any(|installed_component| {
+                    installed_component.starts_with(component)
+                        && installed_component.ends_with("(installed)")
+                });
+                (component, is_installed)
+            })
+            .collect())
+    }
+
+    /// Whether `rustup` has all of the [`REQUIRED_COMPONENTS`] installed in the toolchain.
+    pub fn all_required_toolchain_components_installed(&self) -> anyhow::Result<bool> {
+        Ok(self
+            .required_components_status()?
+            .iter()
+            .all(|(_, is_installed)| *is_installed))
+    }
+
     /// Prompt user if they want to install a new Rust toolchain.
     fn get_consent_for_toolchain_install(&self, prompt: &str) -> anyhow::Result<()> {
         if self.is_toolchain_install_consent {
             return Ok(());
         }
         log::debug!("asking for consent to install the required toolchain");
-        crossterm::terminal::enable_raw_mode()?;
-        crate::user_output!("{prompt} [y/n]: ");
-        let input = crossterm::event::read()?;
-        crossterm::terminal::disable_raw_mode()?;
-        crate::user_output!("{:?}\n", input);
-
-        if let crossterm::event::Event::Key(crossterm::event::KeyEvent {
-            code: crossterm::event::KeyCode::Char('y'),
-            ..
-        }) = input
-        {
+        if ask_for_consent(prompt)? {
             Ok(())
         } else {
             crate::user_output!("Exiting...\n");
@@ -186,14 +412,129 @@ impl SpirvCli {
     }
 }
 
+/// Ask the user a yes or no question, returning whether they pressed "y".
+///
+/// The question is written to stderr even with `--quiet`, or when stdout is reserved for eg
+/// JSON messages, as otherwise it would wait for a key without anything shown.
+pub fn ask_for_consent(prompt: &str) -> anyhow::Result<bool> {
+    use std::io::Write as _;
+
+    let mut stderr = std::io::stderr();
+    crossterm::terminal::enable_raw_mode()?;
+    write!(stderr, "{}{prompt} [y/n]: ", crate::user_output_prefix())?;
+    stderr.flush()?;
+    let input = crossterm::event::read()?;
+    crossterm::terminal::disable_raw_mode()?;
+    let is_yes = matches!(
+        input,
+        crossterm::event::Event::Key(crossterm::event::KeyEvent {
+            code: crossterm::event::KeyCode::Char('y'),
+            ..
+        })
+    );
+    writeln!(stderr, "{}", if is_yes { "y" } else { "n" })?;
+
+    Ok(is_yes)
+}
+
+/// The `rustup` commands that install a toolchain, when it's missing, and its missing components.
+fn install_commands(
+    channel: &str,
+    is_toolchain_missing: bool,
+    missing_components: &[&str],
+) -> Vec<String> {
+    let mut commands = Vec::new();
+    if is_toolchain_missing {
+        commands.push(format!("rustup toolchain add {channel}"));
+    }
+    if !missing_components.is_empty() {
+        commands.push(format!(
+            "rustup component add --toolchain {channel} {}",
+            missing_components.join(" ")
+        ));
+    }
+    commands
+}
+
+/// `PATH` with a toolchain's bin directory in front, so that its tools are found first.
+pub fn path_with_toolchain_bin_dir(dir: &std::path::Path) -> anyhow::Result<std::ffi::OsString> {
+    let existing_paths = std::env::var_os("PATH").unwrap_or_default();
+    let paths = core::iter::once(dir.to_path_buf()).chain(std::env::split_paths(&existing_paths));
+    std::env::join_paths(paths).with_context(|| {
+        format!(
+            "the toolchain bin directory '{}' can't be added to `PATH`",
+            dir.display()
+        )
+    })
+}
+
+/// The path of one of a toolchain's tools in its bin directory.
+fn toolchain_bin_path(dir: &std::path::Path, tool: &str) -> std::path::PathBuf {
+    dir.join(format!("{tool}{}", std::env::consts::EXE_SUFFIX))
+}
+
+#[cfg(test)]
+impl SpirvCli {
+    /// A crates.io `spirv-std` 0.9.0 with the `nightly-2024-04-24` toolchain, without resolving
+    /// anything, for tests that only need a `SpirvCli` to pass around.
+    pub fn for_tests() -> Self {
+        Self {
+            source: SpirvSource::CratesIO("0.9.0".to_owned()),
+            channel: "nightly-2024-04-24".to_owned(),
+            date: chrono::NaiveDate::from_ymd_opt(2024, 4, 24).unwrap(),
+            is_toolchain_install_consent: true,
+            toolchain_bin_dir: None,
+            rustup: RUSTUP.into(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test_log::test]
+    fn toolchain_commands_from_bin_dir() {
+        let mut spirv_cli = SpirvCli::for_tests();
+        let rustup_command = spirv_cli.toolchain_command("cargo").unwrap();
+        assert_eq!(
+            vec!["+nightly-2024-04-24"],
+            rustup_command.get_args().collect::<Vec<_>>()
+        );
+        assert_eq!(0, rustup_command.get_envs().count());
+
+        let dir = std::path::PathBuf::from("toolchain").join("bin");
+        spirv_cli.toolchain_bin_dir = Some(dir.clone());
+        let command = spirv_cli.toolchain_command("cargo").unwrap();
+        assert_eq!(toolchain_bin_path(&dir, "cargo"), command.get_program());
+        assert_eq!(0, command.get_args().count());
+        let envs = command
+            .get_envs()
+            .collect::<std::collections::HashMap<_, _>>();
+        let rustc = toolchain_bin_path(&dir, "rustc");
+        assert_eq!(
+            Some(&Some(rustc.as_os_str())),
+            envs.get(std::ffi::OsStr::new("RUSTC"))
+        );
+        let path = envs
+            .get(std::ffi::OsStr::new("PATH"))
+            .copied()
+            .flatten()
+            .unwrap();
+        assert_eq!(Some(dir), std::env::split_paths(path).next());
+    }
+
     #[test_log::test]
     fn cached_checkout_dir_sanity() {
         let shader_template_path = crate::test::shader_crate_template_path();
-        let spirv = SpirvCli::new(&shader_template_path, None, None, None, true).unwrap();
+        let spirv = SpirvCli::new(
+            &shader_template_path,
+            SpirvCliOptions {
+                is_toolchain_install_consent: true,
+                ..SpirvCliOptions::default()
+            },
+        )
+        .unwrap();
         let dir = spirv.cached_checkout_path().unwrap();
         let name = dir
             .file_name()
@@ -202,8 +543,162 @@ mod test {
             .map(std::string::ToString::to_string)
             .unwrap();
         assert_eq!(
-            "https___github_com_Rust-GPU_rust-gpu+82a0f69+nightly-2024-04-24",
+            "https___github_com_Rust-GPU_rust-gpu+82a0f69-ddd241e7d4ee52b8+nightly-2024-04-24",
             &name
         );
+        assert_eq!(installs_dir(&host()).unwrap(), dir.parent().unwrap());
+    }
+
+    #[test_log::test]
+    fn toolchain_install_commands() {
+        assert_eq!(
+            vec![
+                "rustup toolchain add nightly-2024-04-24",
+                "rustup component add --toolchain nightly-2024-04-24 rust-src rustc-dev llvm-tools",
+            ],
+            install_commands("nightly-2024-04-24", true, &REQUIRED_COMPONENTS)
+        );
+        assert_eq!(
+            vec!["rustup component add --toolchain nightly-2024-04-24 llvm-tools"],
+            install_commands("nightly-2024-04-24", false, &["llvm-tools"])
+        );
+        assert!(install_commands("nightly-2024-04-24", false, &[]).is_empty());
+    }
+
+    /// Write a fake `rustup` to `dir` that appends its arguments to `rustup.log` and prints the
+    /// given `toolchain list` and `component list` outputs.
+    #[cfg(unix)]
+    fn fake_rustup(dir: &std::path::Path, toolchains: &str, components: &str) -> SpirvCli {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let rustup = dir.join("rustup");
+        std::fs::write(
+            &rustup,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> '{}'\ncase \"$1 $2\" in\n\
+                 'toolchain list') printf '{toolchains}' ;;\n\
+                 'component list') printf '{components}' ;;\nesac\n",
+                dir.join("rustup.log").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&rustup, std::fs::Permissions::from_mode(0o755)).unwrap();
+        SpirvCli {
+            rustup,
+            ..SpirvCli::for_tests()
+        }
+    }
+
+    /// The invocations of a [`fake_rustup`] in `dir`.
+    #[cfg(unix)]
+    fn rustup_log(dir: &std::path::Path) -> Vec<String> {
+        std::fs::read_to_string(dir.join("rustup.log"))
+            .unwrap()
+            .lines()
+            .map(ToOwned::to_owned)
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test_log::test]
+    fn installing_missing_toolchains() {
+        let dir = crate::test::fixture_dir("fake-rustup-missing");
+        let spirv_cli = fake_rustup(&dir, "stable-x86_64-unknown-linux-gnu\\n", "");
+        spirv_cli.ensure_toolchain_and_components_exist().unwrap();
+        assert_eq!(
+            vec![
+                "toolchain list",
+                "toolchain add nightly-2024-04-24",
+                "component list --toolchain nightly-2024-04-24",
+                "component add --toolchain nightly-2024-04-24 rust-src rustc-dev llvm-tools",
+            ],
+            rustup_log(&dir)
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[cfg(unix)]
+    #[test_log::test]
+    fn skipping_installed_toolchains() {
+        let dir = crate::test::fixture_dir("fake-rustup-installed");
+        let spirv_cli = fake_rustup(
+            &dir,
+            "nightly-2024-04-24-x86_64-unknown-linux-gnu\\n",
+            "rust-src (installed)\\nrustc-dev-x86_64-unknown-linux-gnu (installed)\\n\
+             llvm-tools-x86_64-unknown-linux-gnu (installed)\\n",
+        );
+        spirv_cli.ensure_toolchain_and_components_exist().unwrap();
+        assert_eq!(
+            vec![
+                "toolchain list",
+                "component list --toolchain nightly-2024-04-24"
+            ],
+            rustup_log(&dir)
+        );
+        assert!(spirv_cli.toolchain_install_commands().unwrap().is_empty());
+        crate::test::tests_teardown();
+    }
+
+    #[cfg(unix)]
+    #[test_log::test]
+    fn listing_installed_toolchains() {
+        let dir = crate::test::fixture_dir("fake-rustup-list");
+        let spirv_cli = fake_rustup(
+            &dir,
+            "stable-x86_64-unknown-linux-gnu (default)\\nnightly-2024-04-24-x86_64-unknown-linux-gnu\\n",
+            "",
+        );
+        assert!(spirv_cli.is_rustup_installed());
+        assert_eq!(
+            vec![
+                "stable-x86_64-unknown-linux-gnu",
+                "nightly-2024-04-24-x86_64-unknown-linux-gnu"
+            ],
+            spirv_cli.installed_toolchains().unwrap()
+        );
+        assert!(spirv_cli.is_toolchain_installed().unwrap());
+        assert_eq!(
+            vec!["--version", "toolchain list", "toolchain list"],
+            rustup_log(&dir)
+        );
+
+        let missing = SpirvCli {
+            rustup: dir.join("missing-rustup"),
+            ..spirv_cli
+        };
+        assert!(!missing.is_rustup_installed());
+        missing.installed_toolchains().unwrap_err();
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn missing_rustup_errors() {
+        let missing = rustup_error(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(missing.to_string().contains("https://rustup.rs"));
+        let other = rustup_error(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert_eq!("could not run `rustup`", other.to_string());
+    }
+
+    #[test_log::test]
+    fn installs_dirs_per_host() {
+        let linux = installs_dir("x86_64-linux-gnu").unwrap();
+        let macos = installs_dir("aarch64-macos").unwrap();
+        assert_ne!(linux, macos);
+        assert_eq!(linux.parent(), macos.parent());
+        assert!(host().starts_with(&format!(
+            "{}-{}",
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        )));
+
+        let gnu = host_from("x86_64", "linux", "gnu");
+        let musl = host_from("x86_64", "linux", "musl");
+        assert_eq!("x86_64-linux-gnu", gnu);
+        assert_ne!(installs_dir(&gnu).unwrap(), installs_dir(&musl).unwrap());
+        assert_ne!(
+            host_from("x86_64", "windows", "msvc"),
+            host_from("x86_64", "windows", "gnu")
+        );
+        assert_eq!("aarch64-macos", host_from("aarch64", "macos", ""));
     }
 }
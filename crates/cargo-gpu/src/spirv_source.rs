@@ -2,17 +2,45 @@
 //!
 //! We do this by calling `cargo tree` inside the shader's crate to get the defined `spirv-std`
 //! version. Then with that we `git checkout` the `rust-gpu` repo that corresponds to that version.
-//! From there we can look at the source code to get the required Rust toolchain.
+//! From there we can look at the source code to get the required Rust toolchain. Crates.io
+//! releases are instead read from the published crates, without a checkout.
 
 use anyhow::Context as _;
 
 /// The canonical `rust-gpu` URI
 const RUST_GPU_REPO: &str = "https://github.com/Rust-GPU/rust-gpu";
 
+/// The file that caches the toolchain channel of a crates.io release of `rust-gpu`.
+const TOOLCHAIN_CHANNEL_FILENAME: &str = "toolchain-channel.txt";
+
+/// Short names for well-known `rust-gpu` repos, for `--spirv-builder-source-alias`.
+pub const SOURCE_ALIASES: &[(&str, &str)] = &[
+    ("rust-gpu", RUST_GPU_REPO),
+    ("embark", "https://github.com/EmbarkStudios/rust-gpu"),
+];
+
+/// Expand a `--spirv-builder-source-alias` to the URL of its repo.
+pub fn parse_source_alias(alias: &str) -> Result<String, String> {
+    SOURCE_ALIASES
+        .iter()
+        .find(|(name, _)| *name == alias)
+        .map(|(_, url)| (*url).to_owned())
+        .ok_or_else(|| {
+            let names = SOURCE_ALIASES
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>();
+            format!(
+                "'{alias}' is not a known source alias, expected one of: {}",
+                names.join(", ")
+            )
+        })
+}
+
 /// The various sources that the `rust-gpu` repo can have.
 /// Most commonly it will simply be the canonical version on crates.io. But it could also be the
 /// Git version, or a fork.
-#[derive(Eq, PartialEq, Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum SpirvSource {
     /// If the shader specifies a simple version like `spirv-std = "0.9.0"` then the source of
     /// `rust-gpu` is the conventional crates.io version.
@@ -51,21 +79,215 @@ impl core::fmt::Display for SpirvSource {
 impl SpirvSource {
     /// Look into the shader crate to get the version of `rust-gpu` it's using.
     pub fn get_rust_gpu_deps_from_shader(
-        shader_crate_path: &std::path::PathBuf,
+        shader_crate_path: &std::path::Path,
+        maybe_channel: Option<String>,
+        is_channel_requeried: bool,
     ) -> anyhow::Result<(Self, chrono::NaiveDate, String)> {
-        let rust_gpu_source = Self::get_spirv_std_dep_definition(shader_crate_path)?;
+        Self::get_spirv_std_dep_definition(shader_crate_path)?
+            .get_rust_gpu_deps(maybe_channel, is_channel_requeried)
+    }
 
-        rust_gpu_source.ensure_repo_is_installed()?;
-        rust_gpu_source.checkout()?;
+    /// A crates.io source for an explicit `spirv-std` version, which must be a semantic version
+    /// like "0.9.0".
+    pub fn from_crates_io_version(version: &str) -> anyhow::Result<Self> {
+        let parsed =
+            semver::Version::parse(version.trim_start_matches('v')).with_context(|| {
+                format!("`spirv-std` version '{version}' is not a semantic version")
+            })?;
+        // Match the "v0.9.0" form that `cargo tree` reports, which is also the repo's tag.
+        Ok(Self::CratesIO(format!("v{parsed}")))
+    }
+
+    /// Get the date of the source's version of `rust-gpu` and the toolchain channel that it
+    /// needs.
+    ///
+    /// Crates.io releases are read from the published `rustc_codegen_spirv` crate in cargo's
+    /// registry, so they never need the `rust-gpu` repo. Other sources are looked up in a
+    /// checkout of their repo.
+    ///
+    /// A `maybe_channel` pins the channel instead, so that the repo's `rust-toolchain.toml`
+    /// isn't parsed at all. The channel of a crates.io release is cached, unless
+    /// `is_channel_requeried`.
+    pub fn get_rust_gpu_deps(
+        self,
+        maybe_channel: Option<String>,
+        is_channel_requeried: bool,
+    ) -> anyhow::Result<(Self, chrono::NaiveDate, String)> {
+        let rust_gpu_source = self;
 
-        let date = rust_gpu_source.get_version_date()?;
-        let channel = Self::get_channel_from_toolchain_toml(&rust_gpu_source.to_dirname()?)?;
+        let (date, maybe_required_channel) = if let Self::CratesIO(version) = &rust_gpu_source {
+            let required_channel =
+                rust_gpu_source.get_cached_channel_from_registry(version, is_channel_requeried)?;
+            (
+                Self::get_channel_date(&required_channel)?,
+                Some(required_channel),
+            )
+        } else {
+            rust_gpu_source.ensure_repo_is_installed()?;
+            rust_gpu_source.checkout()?;
+            (rust_gpu_source.get_version_date()?, None)
+        };
+        let channel = if let Some(channel) = maybe_channel {
+            log::info!(
+                "using the pinned toolchain channel {channel}, \
+                 rather than the one that `rust-gpu` requires"
+            );
+            channel
+        } else if let Some(required_channel) = maybe_required_channel {
+            required_channel
+        } else {
+            Self::get_channel_from_toolchain_toml(&rust_gpu_source.to_dirname()?)?
+        };
 
         log::debug!("Parsed version, date and toolchain channel from shader-defined `rust-gpu`: {rust_gpu_source:?}, {date}, {channel}");
 
         Ok((rust_gpu_source, date, channel))
     }
 
+    /// The directory of the scratch crate that resolves a crates.io release of `rust-gpu`.
+    fn registry_query_dir(&self) -> anyhow::Result<std::path::PathBuf> {
+        Ok(crate::cache_dir()?.join("crates-io").join(self.cache_key()))
+    }
+
+    /// Get the toolchain channel that the crates.io release `version` of `rust-gpu` requires,
+    /// from the [`TOOLCHAIN_CHANNEL_FILENAME`] next to the scratch crate that it was first
+    /// found with. A release never changes, so the file is only replaced when
+    /// `is_channel_requeried`.
+    fn get_cached_channel_from_registry(
+        &self,
+        tagged_version: &str,
+        is_channel_requeried: bool,
+    ) -> anyhow::Result<String> {
+        let channel_path = self.registry_query_dir()?.join(TOOLCHAIN_CHANNEL_FILENAME);
+        if !is_channel_requeried {
+            if let Ok(contents) = std::fs::read_to_string(&channel_path) {
+                let cached = contents.trim();
+                if !cached.is_empty() {
+                    log::debug!(
+                        "using the toolchain channel {cached} cached in '{}'",
+                        channel_path.display()
+                    );
+                    return Ok(cached.to_owned());
+                }
+            }
+        }
+
+        let channel = self.get_channel_from_registry(tagged_version)?;
+        std::fs::write(&channel_path, &channel).with_context(|| {
+            format!(
+                "could not cache the toolchain channel in '{}'",
+                channel_path.display()
+            )
+        })?;
+        Ok(channel)
+    }
+
+    /// Get the toolchain channel that the crates.io release `version` of `rust-gpu` requires.
+    ///
+    /// The published crates don't include the repo's `rust-toolchain.toml`, but the build
+    /// script of `rustc_codegen_spirv` embeds a copy of it. So we have cargo resolve that exact
+    /// release in a scratch crate and read the build script from the registry source that
+    /// `cargo metadata` points at.
+    fn get_channel_from_registry(&self, tagged_version: &str) -> anyhow::Result<String> {
+        let version = tagged_version.trim_start_matches('v');
+        let query_dir = self.registry_query_dir()?;
+        std::fs::create_dir_all(query_dir.join("src"))?;
+        std::fs::write(
+            query_dir.join("Cargo.toml"),
+            format!(
+                "[package]\n\
+                 name = \"rust-gpu-release-query\"\n\
+                 version = \"0.0.0\"\n\
+                 edition = \"2021\"\n\
+                 \n\
+                 [dependencies]\n\
+                 rustc_codegen_spirv = \"={version}\"\n\
+                 \n\
+                 [workspace]\n"
+            ),
+        )?;
+        std::fs::write(query_dir.join("src").join("lib.rs"), "")?;
+
+        log::debug!(
+            "Resolving `rustc_codegen_spirv` {version} from crates.io in {}",
+            query_dir.display()
+        );
+        // `--offline` is passed on through `CARGO_NET_OFFLINE`, in which case this only works
+        // once the release is in cargo's registry cache.
+        let output_metadata = std::process::Command::new("cargo")
+            .current_dir(&query_dir)
+            .args(["metadata", "--format-version", "1", "--manifest-path"])
+            .arg(query_dir.join("Cargo.toml"))
+            .output()?;
+        anyhow::ensure!(
+            output_metadata.status.success(),
+            "couldn't get `rustc_codegen_spirv` {version} from crates.io:\n{}",
+            crate::stderr_tail(&output_metadata.stderr)
+        );
+        let metadata: serde_json::Value = serde_json::from_slice(&output_metadata.stdout)?;
+        let build_script = metadata
+            .get("packages")
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten()
+            .find(|package| {
+                package.get("name").and_then(serde_json::Value::as_str)
+                    == Some("rustc_codegen_spirv")
+            })
+            .and_then(|package| package.get("manifest_path")?.as_str())
+            .and_then(|manifest_path| std::path::Path::new(manifest_path).parent())
+            .map(|dir| dir.join("build.rs"))
+            .with_context(|| {
+                format!("`cargo metadata` doesn't list `rustc_codegen_spirv` {version}")
+            })?;
+
+        log::debug!(
+            "Parsing {} for the required toolchain",
+            build_script.display()
+        );
+        let contents = std::fs::read_to_string(&build_script)
+            .with_context(|| format!("could not read '{}'", build_script.display()))?;
+        Self::get_channel_from_build_script(&contents).with_context(|| {
+            format!(
+                "couldn't find the required toolchain channel in '{}'",
+                build_script.display()
+            )
+        })
+    }
+
+    /// Find the `channel = "..."` line of the `REQUIRED_RUST_TOOLCHAIN` that
+    /// `rustc_codegen_spirv`'s build script embeds.
+    fn get_channel_from_build_script(contents: &str) -> Option<String> {
+        contents.lines().find_map(|line| {
+            let value = line.trim().strip_prefix("channel")?.trim_start();
+            let quoted = value.strip_prefix('=')?.trim().strip_prefix('"')?;
+            quoted
+                .split_once('"')
+                .map(|(channel, _)| channel.to_owned())
+        })
+    }
+
+    /// The date of a nightly channel like "nightly-2023-05-27". A release can't require a
+    /// nightly from after it was made, so for crates.io releases it stands in for the date of
+    /// the version.
+    fn get_channel_date(channel: &str) -> anyhow::Result<chrono::NaiveDate> {
+        let date = channel
+            .strip_prefix("nightly-")
+            .with_context(|| format!("the required toolchain '{channel}' isn't a dated nightly"))?;
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .with_context(|| format!("couldn't parse the date of toolchain '{channel}'"))
+    }
+
+    /// The root of the `rust-gpu` repo containing a local `spirv-std` crate, which is the
+    /// closest ancestor with a `crates/spirv-builder` directory. We search upwards rather than
+    /// assuming that `spirv-std` is at `crates/spirv-std`, as it may be symlinked or laid out
+    /// differently.
+    pub fn find_local_repo_root(spirv_std_path: &std::path::Path) -> Option<&std::path::Path> {
+        spirv_std_path
+            .ancestors()
+            .find(|ancestor| ancestor.join("crates").join("spirv-builder").is_dir())
+    }
+
     /// Convert the source to just its version.
     pub fn to_version(&self) -> String {
         match self {
@@ -74,12 +296,38 @@ impl SpirvSource {
         }
     }
 
-    /// Convert the source to just its repo or path.
-    fn to_repo(&self) -> String {
-        match self {
+    /// Convert the source to just its repo, or the root of the repo that a local `spirv-std`
+    /// is in.
+    fn to_repo(&self) -> anyhow::Result<String> {
+        Ok(match self {
             Self::CratesIO(_) => RUST_GPU_REPO.to_owned(),
             Self::Git { url, .. } => url.to_owned(),
-            Self::Path((path, _)) => path.to_owned(),
+            Self::Path((path, _)) => Self::find_local_repo_root(std::path::Path::new(path))
+                .with_context(|| {
+                    format!(
+                        "the local `spirv-std` at '{path}' isn't inside a `rust-gpu` repo, as none \
+                         of its ancestors contain `crates/spirv-builder`"
+                    )
+                })?
+                .display()
+                .to_string(),
+        })
+    }
+
+    /// A name for the source that can be used as a directory in the cache, and that is distinct
+    /// for distinct sources.
+    ///
+    /// The readable `url+rev` form isn't enough for that, as a URL or path can contain `+`
+    /// itself and sanitising it for a directory name merges more characters. So sources other
+    /// than crates.io versions get a hash of their unsanitised parts appended.
+    pub fn cache_key(&self) -> String {
+        let readable = crate::to_dirname(self.to_string().as_ref());
+        match self {
+            Self::CratesIO(_) => readable,
+            Self::Git { url, rev } => format!("{readable}-{:016x}", stable_hash(&[url, rev])),
+            Self::Path((path, version)) => {
+                format!("{readable}-{:016x}", stable_hash(&[path, version]))
+            }
         }
     }
 
@@ -87,8 +335,9 @@ impl SpirvSource {
     /// It needs to be dynamically created because an end-user might want to swap out the source,
     /// maybe using their own fork for example.
     fn to_dirname(&self) -> anyhow::Result<std::path::PathBuf> {
-        let dir = crate::to_dirname(self.to_string().as_ref());
-        Ok(crate::cache_dir()?.join("rust-gpu-repo").join(dir))
+        Ok(crate::cache_dir()?
+            .join("rust-gpu-repo")
+            .join(self.cache_key()))
     }
 
     /// Checkout the `rust-gpu` repo to the requested version.
@@ -98,15 +347,17 @@ impl SpirvSource {
             self.to_dirname()?.display(),
             self.to_version()
         );
+        verify_revision(&self.to_dirname()?, &self.to_version())?;
         let output_checkout = std::process::Command::new("git")
             .current_dir(self.to_dirname()?)
             .args(["checkout", self.to_version().as_ref()])
             .output()?;
         anyhow::ensure!(
             output_checkout.status.success(),
-            "couldn't checkout revision '{}' of `rust-gpu` at {}",
+            "couldn't checkout revision '{}' of `rust-gpu` at {}:\n{}",
             self.to_version(),
-            self.to_dirname()?.to_string_lossy()
+            self.to_dirname()?.to_string_lossy(),
+            crate::stderr_tail(&output_checkout.stderr)
         );
 
         Ok(())
@@ -133,9 +384,10 @@ impl SpirvSource {
             .output()?;
         anyhow::ensure!(
             output_date.status.success(),
-            "couldn't get `rust-gpu` version date at for {} at {}",
+            "couldn't get `rust-gpu` version date at for {} at {}:\n{}",
             self.to_version(),
-            self.to_dirname()?.to_string_lossy()
+            self.to_dirname()?.to_string_lossy(),
+            crate::stderr_tail(&output_date.stderr)
         );
         let date_string = String::from_utf8_lossy(&output_date.stdout)
             .to_string()
@@ -171,51 +423,112 @@ impl SpirvSource {
         Ok(channel.to_string().replace('"', ""))
     }
 
+    /// The `Cargo.lock` of the shader crate's workspace, the closest one in the directory of its
+    /// `Cargo.toml` or an ancestor.
+    fn find_lockfile(manifest_dir: &std::path::Path) -> Option<std::path::PathBuf> {
+        manifest_dir
+            .ancestors()
+            .map(|dir| dir.join("Cargo.lock"))
+            .find(|path| path.is_file())
+    }
+
+    /// Get the shader crate's `spirv-std` source exactly as it's locked in its `Cargo.lock`,
+    /// rather than as `cargo tree` resolves it, which can differ when the lockfile is stale.
+    ///
+    /// A lockfile doesn't record the paths of path dependencies, so those are still resolved
+    /// with `cargo tree`.
+    pub fn get_spirv_std_dep_from_lockfile(
+        shader_crate_path: &std::path::Path,
+    ) -> anyhow::Result<Self> {
+        let manifest_path = crate::cargo_manifest_path(shader_crate_path)
+            .context("could not find the shader crate's `Cargo.toml`")?;
+        let lockfile = manifest_path
+            .parent()
+            .and_then(Self::find_lockfile)
+            .with_context(|| {
+                format!(
+                    "could not find a `Cargo.lock` for '{}', create one with `cargo generate-lockfile`",
+                    manifest_path.display()
+                )
+            })?;
+        let mut packages = LockedPackage::read_spirv_std(&lockfile)?;
+        anyhow::ensure!(
+            packages.len() <= 1,
+            "'{}' locks more than one `spirv-std` package",
+            lockfile.display()
+        );
+        let package = packages
+            .pop()
+            .with_context(|| format!("`spirv-std` is not locked in '{}'", lockfile.display()))?;
+        let source = package.to_spirv_source();
+        log::debug!(
+            "`spirv-std` is locked in '{}' to {source:?}",
+            lockfile.display()
+        );
+        source.map_or_else(|| Self::get_spirv_std_dep_definition(shader_crate_path), Ok)
+    }
+
     /// Get the shader crate's `spirv_std = ...` definition in its `Cargo.toml`
     pub fn get_spirv_std_dep_definition(
-        shader_crate_path: &std::path::PathBuf,
+        shader_crate_path: &std::path::Path,
     ) -> anyhow::Result<Self> {
-        let cwd = std::env::current_dir().context("no cwd")?;
-        let exec_path = if shader_crate_path.is_absolute() {
-            shader_crate_path.clone()
-        } else {
-            cwd.join(shader_crate_path)
-        }
-        .canonicalize()
-        .context("could not get absolute path to shader crate")?;
-        if !exec_path.is_dir() {
-            log::error!("{exec_path:?} is not a directory, aborting");
-            anyhow::bail!("{exec_path:?} is not a directory");
-        }
+        let manifest_path = crate::cargo_manifest_path(shader_crate_path)
+            .context("could not find the shader crate's `Cargo.toml`")?;
+        // Rustup picks the toolchain from the working directory, so we still run from the crate.
+        let exec_path = manifest_path
+            .parent()
+            .context("shader crate's `Cargo.toml` has no parent directory")?;
 
-        log::debug!("Running `cargo tree` on {}", exec_path.display());
+        log::debug!("Running `cargo tree` on {}", manifest_path.display());
         let output_cargo_tree = std::process::Command::new("cargo")
-            .current_dir(&exec_path)
+            .current_dir(exec_path)
             .args(["tree", "--workspace", "--prefix", "none"])
+            .arg("--manifest-path")
+            .arg(&manifest_path)
             .output()?;
         anyhow::ensure!(
             output_cargo_tree.status.success(),
-            "could not query shader's `Cargo.toml` for `spirv-std` dependency"
+            "could not query shader's `Cargo.toml` for `spirv-std` dependency:\n{}",
+            crate::stderr_tail(&output_cargo_tree.stderr)
         );
         let cargo_tree_string = String::from_utf8_lossy(&output_cargo_tree.stdout);
 
+        // `cargo tree` lists the real package name first, even when the shader crate renames
+        // the dependency with `package = "spirv-std"`. Matching it exactly avoids picking up
+        // crates like `spirv-std-macros`, or a shader crate that has `spirv-std` in its name.
         let maybe_spirv_std_def = cargo_tree_string
             .lines()
-            .find(|line| line.contains("spirv-std"));
+            .find(|line| line.split_whitespace().next() == Some("spirv-std"));
         log::trace!("  found {maybe_spirv_std_def:?}");
 
         let Some(spirv_std_def) = maybe_spirv_std_def else {
-            anyhow::bail!("`spirv-std` not found in shader's `Cargo.toml` at {exec_path:?}:\n{cargo_tree_string}");
+            log::debug!(
+                "`cargo tree` of '{}':\n{cargo_tree_string}",
+                exec_path.display()
+            );
+            anyhow::bail!(
+                "the crate at '{}' doesn't depend on `spirv-std`, so it doesn't appear to be a \
+                 `rust-gpu` shader crate. Check that `--shader-crate` points at the shader \
+                 crate, and that `spirv-std` is in the `[dependencies]` of its `Cargo.toml`",
+                exec_path.display()
+            );
         };
 
-        Self::parse_spirv_std_source_and_version(spirv_std_def)
+        let maybe_lockfile = Self::find_lockfile(exec_path);
+        Self::parse_spirv_std_source_and_version(spirv_std_def, maybe_lockfile.as_deref())
     }
 
     /// Parse a string like:
     ///   `spirv-std v0.9.0 (https://github.com/Rust-GPU/rust-gpu?rev=54f6978c#54f6978c) (*)`
     /// Which would return:
     ///   `SpirvSource::Git("https://github.com/Rust-GPU/rust-gpu", "54f6978c")`
-    fn parse_spirv_std_source_and_version(spirv_std_def: &str) -> anyhow::Result<Self> {
+    ///
+    /// Git sources don't always have a `#<rev>` fragment, eg a dependency on a repo's default
+    /// branch with some versions of cargo, in which case the revision is read from `lockfile`.
+    fn parse_spirv_std_source_and_version(
+        spirv_std_def: &str,
+        lockfile: Option<&std::path::Path>,
+    ) -> anyhow::Result<Self> {
         log::trace!("parsing spirv-std source and version from def: '{spirv_std_def}'");
         let parts: Vec<String> = spirv_std_def.split_whitespace().map(String::from).collect();
         let version = parts
@@ -242,7 +555,12 @@ impl SpirvSource {
                 let splits = source_string.split('#');
                 splits.last().map(std::borrow::ToOwned::to_owned)
             } else {
-                None
+                lockfile.and_then(|path| {
+                    Self::read_locked_git_rev(path, &source_string)
+                        .map_err(|error| log::warn!("{error:?}"))
+                        .ok()
+                        .flatten()
+                })
             };
             if uri.scheme().is_some() {
                 source = Self::parse_git_source(version, &uri, maybe_hash)?;
@@ -256,6 +574,31 @@ impl SpirvSource {
         Ok(source)
     }
 
+    /// Read the revision that `spirv-std` from the Git source `source` (without its fragment) is
+    /// locked to in a `Cargo.lock`, ie the commit hash after the `#` of its locked source:
+    ///   `git+https://github.com/Rust-GPU/rust-gpu#54f6978cd8fbb1fd1d0ba1dd77ff6a3c5ed63fa3`
+    fn read_locked_git_rev(
+        lockfile: &std::path::Path,
+        source: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let locked_source = format!("git+{source}");
+        let maybe_rev = LockedPackage::read_spirv_std(lockfile)?
+            .into_iter()
+            .filter_map(|package| {
+                package
+                    .source?
+                    .split_once('#')
+                    .map(|(url, rev)| (url.to_owned(), rev.to_owned()))
+            })
+            .find(|(url, _)| *url == locked_source)
+            .map(|(_, rev)| rev);
+        log::debug!(
+            "locked revision of `spirv-std` from {source} in '{}': {maybe_rev:?}",
+            lockfile.display()
+        );
+        Ok(maybe_rev)
+    }
+
     /// Parse a Git source like: `https://github.com/Rust-GPU/rust-gpu?rev=54f6978c#54f6978c`
     fn parse_git_source(
         version: String,
@@ -279,36 +622,38 @@ impl SpirvSource {
     }
 
     /// Decide the Git revision to use.
+    ///
+    /// The fragment is the commit that cargo resolved the dependency to, whether it was given
+    /// with `?rev=`, `?branch=` or `?tag=`, so it's preferred. Without one, the given revision,
+    /// branch or tag can be checked out itself, and failing that the version's tag.
     fn parse_git_revision(
         maybe_query: Option<&str>,
         maybe_fragment: Option<String>,
         version: String,
     ) -> String {
-        let marker = "rev=";
-        let maybe_sane_query = maybe_query.and_then(|query| {
-            // TODO: This might seem a little crude, but it saves adding a whole query parsing dependency.
-            let sanity_check = query.contains(marker) && query.split('=').count() == 2;
-            sanity_check.then_some(query)
-        });
-
-        if let Some(query) = maybe_sane_query {
-            return query.replace(marker, "");
-        }
-
         if let Some(fragment) = maybe_fragment {
             return fragment;
         }
 
-        version
+        // This might seem a little crude, but it saves adding a whole query parsing dependency.
+        let maybe_ref = maybe_query.and_then(|query| {
+            query.split('&').find_map(|parameter| {
+                let (key, value) = parameter.split_once('=')?;
+                (["rev", "branch", "tag"].contains(&key) && !value.is_empty())
+                    .then(|| value.to_owned())
+            })
+        });
+        maybe_ref.unwrap_or(version)
     }
 
     /// `git clone` the `rust-gpu` repo. We use it to get the required Rust toolchain to compile
     /// the shader.
     fn ensure_repo_is_installed(&self) -> anyhow::Result<()> {
+        let repo = self.to_repo()?;
         if self.to_dirname()?.exists() {
             log::debug!(
                 "Not cloning `rust-gpu` repo ({}) as it already exists at {}",
-                self.to_repo(),
+                repo,
                 self.to_dirname()?.to_string_lossy().as_ref(),
             );
             return Ok(());
@@ -316,16 +661,32 @@ impl SpirvSource {
 
         log::debug!(
             "Cloning `rust-gpu` repo {} to {}",
-            self.to_repo(),
+            repo,
             self.to_dirname()?.to_string_lossy().as_ref(),
         );
 
-        crate::user_output!("Cloning `rust-gpu` repo...");
+        anyhow::ensure!(
+            !crate::is_offline(),
+            "`rust-gpu` repo {} isn't cloned to {} yet, and `--offline` prevents cloning it",
+            repo,
+            self.to_dirname()?.display()
+        );
+
+        // On stderr, like cargo's own progress, as the clone can be part of a command whose
+        // stdout is reserved, eg for the names of `cargo gpu show entry-points`.
+        if !crate::is_quiet() {
+            use std::io::Write as _;
+            writeln!(
+                std::io::stderr(),
+                "{}Cloning `rust-gpu` repo...",
+                crate::user_output_prefix()
+            )?;
+        }
 
         let output_clone = std::process::Command::new("git")
             .args([
                 "clone",
-                self.to_repo().as_ref(),
+                repo.as_ref(),
                 self.to_dirname()?.to_string_lossy().as_ref(),
             ])
             .output()?;
@@ -333,7 +694,7 @@ impl SpirvSource {
         anyhow::ensure!(
             output_clone.status.success(),
             "couldn't clone `rust-gpu` {} to {}\n{}",
-            self.to_repo(),
+            repo,
             self.to_dirname()?.to_string_lossy(),
             String::from_utf8_lossy(&output_clone.stderr)
         );
@@ -342,6 +703,103 @@ impl SpirvSource {
     }
 }
 
+/// A 64-bit FNV-1a hash of some strings, which unlike `std`'s hashers is stable across Rust
+/// releases, so it can be used in the names of cached directories. Each string is followed by a
+/// nul byte so that moving characters between the strings changes the hash.
+fn stable_hash(parts: &[&str]) -> u64 {
+    /// The FNV-1a offset basis for 64-bit hashes.
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    /// The FNV-1a prime for 64-bit hashes.
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    parts
+        .iter()
+        .flat_map(|part| part.bytes().chain([0]))
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        })
+}
+
+/// A `spirv-std` package entry in a `Cargo.lock`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct LockedPackage {
+    /// The exact version, like "0.9.0".
+    version: String,
+    /// Where the package comes from, like `registry+https://github.com/rust-lang/crates.io-index`
+    /// or `git+https://github.com/Rust-GPU/rust-gpu?rev=54f6978c#54f6978c...`. Path dependencies
+    /// have no source.
+    source: Option<String>,
+}
+
+impl LockedPackage {
+    /// Read the `spirv-std` packages locked in `lockfile`.
+    fn read_spirv_std(lockfile: &std::path::Path) -> anyhow::Result<Vec<Self>> {
+        let contents = std::fs::read_to_string(lockfile)
+            .with_context(|| format!("could not read '{}'", lockfile.display()))?;
+        let lock: toml::Table = toml::from_str(&contents)
+            .with_context(|| format!("could not parse '{}'", lockfile.display()))?;
+        lock.get("package")
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|package| {
+                package.get("name").and_then(toml::Value::as_str) == Some("spirv-std")
+            })
+            .map(|package| {
+                let version = package
+                    .get("version")
+                    .and_then(toml::Value::as_str)
+                    .with_context(|| {
+                        format!("`spirv-std` has no version in '{}'", lockfile.display())
+                    })?;
+                Ok(Self {
+                    version: version.to_owned(),
+                    source: package
+                        .get("source")
+                        .and_then(toml::Value::as_str)
+                        .map(str::to_owned),
+                })
+            })
+            .collect()
+    }
+
+    /// The `rust-gpu` source of the package, or `None` for a path dependency.
+    fn to_spirv_source(&self) -> Option<SpirvSource> {
+        let source = self.source.as_deref()?;
+        let Some(git_source) = source.strip_prefix("git+") else {
+            return Some(SpirvSource::CratesIO(format!("v{}", self.version)));
+        };
+        // The fragment is the commit that cargo resolved, whatever the query asked for.
+        let (url, rev) = git_source
+            .split_once('#')
+            .unwrap_or((git_source, &self.version));
+        let repo = url.split_once('?').map_or(url, |(repo, _)| repo);
+        Some(SpirvSource::Git {
+            url: repo.to_owned(),
+            rev: rev.to_owned(),
+        })
+    }
+}
+
+/// Ensure that `rev` names a commit in the repo at `repo`, so that an unknown revision is told
+/// apart from `git checkout` failing for other reasons.
+fn verify_revision(repo: &std::path::Path, rev: &str) -> anyhow::Result<()> {
+    let output = std::process::Command::new("git")
+        .current_dir(repo)
+        .args([
+            "rev-parse",
+            "--quiet",
+            "--verify",
+            &format!("{rev}^{{commit}}"),
+        ])
+        .output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "unknown revision '{rev}' of `rust-gpu` at {}, it isn't a commit, branch or tag in the repo",
+        repo.display()
+    );
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -359,11 +817,92 @@ mod test {
         );
     }
 
+    #[test_log::test]
+    fn cache_keys_are_distinct() {
+        let git = |url: &str, rev: &str| SpirvSource::Git {
+            url: url.to_owned(),
+            rev: rev.to_owned(),
+        };
+        let sources = [
+            git("https://github.com/Rust-GPU/rust-gpu", "82a0f69"),
+            git("https://github.com/Rust-GPU/rust-gpu+82a0f69", ""),
+            git("https://github.com/Rust-GPU/rust-gpu", "82a0f69a"),
+            git("https://github.com/Rust-GPU/rust_gpu", "82a0f69"),
+            SpirvSource::Path(("/rust-gpu".to_owned(), "0.9.0".to_owned())),
+            SpirvSource::Path(("/rust-gpu+0".to_owned(), "9.0".to_owned())),
+            SpirvSource::CratesIO("0.9.0".to_owned()),
+        ];
+        let keys: std::collections::BTreeSet<String> =
+            sources.iter().map(SpirvSource::cache_key).collect();
+        assert_eq!(sources.len(), keys.len());
+
+        assert_eq!(
+            "https___github_com_Rust-GPU_rust-gpu+82a0f69-ddd241e7d4ee52b8",
+            sources[0].cache_key()
+        );
+        assert_eq!(
+            "0_9_0",
+            SpirvSource::CratesIO("0.9.0".to_owned()).cache_key()
+        );
+    }
+
+    #[test_log::test]
+    fn parsing_renamed_spirv_std_dep() {
+        let fixture = crate::test::fixture_dir("renamed-spirv-std");
+        let spirv_std_path = fixture.join("spirv-std");
+        let shader_crate_path = fixture.join("renamed-spirv-std-shader");
+        for (path, manifest) in [
+            (
+                &spirv_std_path,
+                "[package]\nname = \"spirv-std\"\nversion = \"9.9.9\"\nedition = \"2021\"\n",
+            ),
+            (
+                &shader_crate_path,
+                "[package]\nname = \"renamed-spirv-std-shader\"\nversion = \"0.1.0\"\n\
+                 edition = \"2021\"\n\n[dependencies]\n\
+                 myspirv = { package = \"spirv-std\", path = \"../spirv-std\" }\n\n[workspace]\n",
+            ),
+        ] {
+            std::fs::create_dir_all(path.join("src")).unwrap();
+            std::fs::write(path.join("Cargo.toml"), manifest).unwrap();
+            std::fs::write(path.join("src/lib.rs"), "").unwrap();
+        }
+
+        let source = SpirvSource::get_spirv_std_dep_definition(&shader_crate_path).unwrap();
+        assert_eq!(
+            source,
+            SpirvSource::Path((
+                spirv_std_path.canonicalize().unwrap().display().to_string(),
+                "v9.9.9".to_owned()
+            ))
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn missing_spirv_std_dep() {
+        let crate_path = crate::test::fixture_dir("not-a-shader-crate");
+        std::fs::create_dir_all(crate_path.join("src")).unwrap();
+        std::fs::write(
+            crate_path.join("Cargo.toml"),
+            "[package]\nname = \"not-a-shader-crate\"\nversion = \"0.1.0\"\n\
+             edition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::write(crate_path.join("src").join("lib.rs"), "").unwrap();
+
+        let error = SpirvSource::get_spirv_std_dep_definition(&crate_path).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("doesn't appear to be a `rust-gpu` shader crate"));
+        assert!(message.contains(&crate_path.canonicalize().unwrap().display().to_string()));
+        crate::test::tests_teardown();
+    }
+
     #[test_log::test]
     fn parsing_spirv_std_dep_for_git_source() {
         let definition =
             "spirv-std v9.9.9 (https://github.com/Rust-GPU/rust-gpu?rev=82a0f69#82a0f69) (*)";
-        let source = SpirvSource::parse_spirv_std_source_and_version(definition).unwrap();
+        let source = SpirvSource::parse_spirv_std_source_and_version(definition, None).unwrap();
         assert_eq!(
             source,
             SpirvSource::Git {
@@ -373,10 +912,63 @@ mod test {
         );
     }
 
+    #[test_log::test]
+    fn parsing_spirv_std_dep_for_git_branches_and_tags() {
+        let git = |rev: &str| SpirvSource::Git {
+            url: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+            rev: rev.to_owned(),
+        };
+        for (query, expected_rev) in [
+            ("?rev=82a0f69#82a0f69abc", "82a0f69abc"),
+            ("?branch=main#82a0f69", "82a0f69"),
+            ("?tag=v0.9.0#82a0f69", "82a0f69"),
+            ("?rev=82a0f69", "82a0f69"),
+            ("?branch=main", "main"),
+            ("?tag=v0.9.0", "v0.9.0"),
+            ("?rev=", "v9.9.9"),
+        ] {
+            let definition =
+                format!("spirv-std v9.9.9 (https://github.com/Rust-GPU/rust-gpu{query}) (*)");
+            assert_eq!(
+                git(expected_rev),
+                SpirvSource::parse_spirv_std_source_and_version(&definition, None).unwrap(),
+                "{definition}"
+            );
+        }
+    }
+
     #[test_log::test]
     fn parsing_spirv_std_dep_for_git_source_hash() {
         let definition = "spirv-std v9.9.9 (https://github.com/Rust-GPU/rust-gpu#82a0f69) (*)";
-        let source = SpirvSource::parse_spirv_std_source_and_version(definition).unwrap();
+        let source = SpirvSource::parse_spirv_std_source_and_version(definition, None).unwrap();
+        assert_eq!(
+            source,
+            SpirvSource::Git {
+                url: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+                rev: "82a0f69".to_owned()
+            }
+        );
+    }
+
+    #[test_log::test]
+    fn parsing_spirv_std_dep_for_git_source_without_fragment() {
+        let dir = crate::test::fixture_dir("fragmentless-git-source");
+        let lockfile = dir.join("Cargo.lock");
+        std::fs::write(
+            &lockfile,
+            "version = 3\n\n\
+             [[package]]\nname = \"spirv-std\"\nversion = \"0.9.0\"\n\
+             source = \"git+https://github.com/Rust-GPU/rust-gpu?branch=main#0123abc\"\n\n\
+             [[package]]\nname = \"spirv-std\"\nversion = \"0.9.0\"\n\
+             source = \"git+https://github.com/Rust-GPU/rust-gpu#82a0f69\"\n\n\
+             [[package]]\nname = \"spirv-std-macros\"\nversion = \"0.9.0\"\n\
+             source = \"git+https://github.com/Rust-GPU/rust-gpu#4567def\"\n",
+        )
+        .unwrap();
+
+        let definition = "spirv-std v0.9.0 (https://github.com/Rust-GPU/rust-gpu) (*)";
+        let source =
+            SpirvSource::parse_spirv_std_source_and_version(definition, Some(&lockfile)).unwrap();
         assert_eq!(
             source,
             SpirvSource::Git {
@@ -384,6 +976,175 @@ mod test {
                 rev: "82a0f69".to_owned()
             }
         );
+
+        // Without a lockfile we can only fall back to the version.
+        let unlocked_source =
+            SpirvSource::parse_spirv_std_source_and_version(definition, None).unwrap();
+        assert_eq!(
+            unlocked_source,
+            SpirvSource::Git {
+                url: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+                rev: "v0.9.0".to_owned()
+            }
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn converting_locked_packages() {
+        let locked = |version: &str, source: Option<&str>| {
+            LockedPackage {
+                version: version.to_owned(),
+                source: source.map(str::to_owned),
+            }
+            .to_spirv_source()
+        };
+        assert_eq!(
+            Some(SpirvSource::CratesIO("v0.9.0".to_owned())),
+            locked(
+                "0.9.0",
+                Some("registry+https://github.com/rust-lang/crates.io-index")
+            )
+        );
+        assert_eq!(
+            Some(SpirvSource::Git {
+                url: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+                rev: "82a0f69008414f51d59184763146caa6850ac588".to_owned()
+            }),
+            locked(
+                "0.9.0",
+                Some("git+https://github.com/Rust-GPU/rust-gpu?rev=82a0f69#82a0f69008414f51d59184763146caa6850ac588")
+            )
+        );
+        assert_eq!(None, locked("0.9.0", None));
+    }
+
+    #[test_log::test]
+    fn spirv_std_version_override() {
+        for version in ["0.9.0", "v0.9.0"] {
+            assert_eq!(
+                SpirvSource::CratesIO("v0.9.0".to_owned()),
+                SpirvSource::from_crates_io_version(version).unwrap()
+            );
+        }
+        SpirvSource::from_crates_io_version("0.9").unwrap_err();
+        SpirvSource::from_crates_io_version("main").unwrap_err();
+    }
+
+    #[test_log::test]
+    fn expanding_source_aliases() {
+        assert_eq!(Ok(RUST_GPU_REPO.to_owned()), parse_source_alias("rust-gpu"));
+        let error = parse_source_alias("rust_gpu").unwrap_err();
+        assert!(error.contains("rust-gpu, embark"), "{error}");
+    }
+
+    #[test_log::test]
+    fn finding_local_repo_root() {
+        let repo = crate::test::fixture_dir("local-rust-gpu");
+        let spirv_std = repo.join("crates").join("spirv-std");
+        let moved_spirv_std = repo.join("vendor").join("std").join("spirv-std");
+        std::fs::create_dir_all(&spirv_std).unwrap();
+        std::fs::create_dir_all(&moved_spirv_std).unwrap();
+        std::fs::create_dir_all(repo.join("crates").join("spirv-builder")).unwrap();
+
+        assert_eq!(
+            Some(repo.as_path()),
+            SpirvSource::find_local_repo_root(&spirv_std)
+        );
+        assert_eq!(
+            Some(repo.as_path()),
+            SpirvSource::find_local_repo_root(&moved_spirv_std)
+        );
+        assert_eq!(
+            None,
+            SpirvSource::find_local_repo_root(repo.parent().unwrap())
+        );
+
+        let outside = SpirvSource::Path((
+            repo.parent().unwrap().display().to_string(),
+            "v0.9.0".to_owned(),
+        ));
+        let error = outside.to_repo().unwrap_err().to_string();
+        assert!(error.contains(&repo.parent().unwrap().display().to_string()));
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn reading_channel_from_codegen_build_script() {
+        let build_script = r##"
+/// Current `rust-toolchain.toml` file
+//const REQUIRED_RUST_TOOLCHAIN: &str = include_str!("../../rust-toolchain.toml");
+const REQUIRED_RUST_TOOLCHAIN: &str = r#"[toolchain]
+channel = "nightly-2023-05-27"
+components = ["rust-src", "rustc-dev", "llvm-tools"]
+# commit_hash = 1a5f8bce74ee432f7cc3aa131bc3d6920e06de10"#;
+"##;
+        assert_eq!(
+            Some("nightly-2023-05-27".to_owned()),
+            SpirvSource::get_channel_from_build_script(build_script)
+        );
+        assert_eq!(
+            None,
+            SpirvSource::get_channel_from_build_script("fn main() {}")
+        );
+        assert_eq!(
+            chrono::NaiveDate::from_ymd_opt(2023, 5, 27).unwrap(),
+            SpirvSource::get_channel_date("nightly-2023-05-27").unwrap()
+        );
+        SpirvSource::get_channel_date("stable").unwrap_err();
+    }
+
+    #[test_log::test]
+    fn crates_io_source_resolves_without_repo_checkout() {
+        let source = SpirvSource::CratesIO("v0.9.0".to_owned());
+        let (resolved, date, channel) = source.clone().get_rust_gpu_deps(None, true).unwrap();
+        assert_eq!(source, resolved);
+        assert_eq!("nightly-2023-05-27", channel);
+        assert_eq!(chrono::NaiveDate::from_ymd_opt(2023, 5, 27).unwrap(), date);
+        assert!(!source.to_dirname().unwrap().exists());
+
+        // The channel is cached, so a stand-in for it is read back until it's requeried.
+        let channel_path = source
+            .registry_query_dir()
+            .unwrap()
+            .join(TOOLCHAIN_CHANNEL_FILENAME);
+        assert_eq!(
+            "nightly-2023-05-27",
+            std::fs::read_to_string(&channel_path).unwrap()
+        );
+        std::fs::write(&channel_path, "nightly-2023-06-01\n").unwrap();
+        let (_, cached_date, cached_channel) =
+            source.clone().get_rust_gpu_deps(None, false).unwrap();
+        assert_eq!("nightly-2023-06-01", cached_channel);
+        assert_eq!(
+            chrono::NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+            cached_date
+        );
+        let (_, _, requeried_channel) = source.get_rust_gpu_deps(None, true).unwrap();
+        assert_eq!("nightly-2023-05-27", requeried_channel);
+    }
+
+    #[test_log::test]
+    fn verifying_revisions() {
+        let repo = crate::test::fixture_dir("verified-revisions");
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .current_dir(&repo)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {args:?}");
+        };
+        git(&["init", "--quiet"]);
+        git(&["commit", "--quiet", "--allow-empty", "--message", "initial"]);
+        git(&["tag", "v0.9.0"]);
+
+        verify_revision(&repo, "HEAD").unwrap();
+        verify_revision(&repo, "v0.9.0").unwrap();
+        let error = verify_revision(&repo, "v0.10.0").unwrap_err().to_string();
+        assert!(error.contains("unknown revision 'v0.10.0'"), "{error}");
+        crate::test::tests_teardown();
     }
 
     #[test_log::test]
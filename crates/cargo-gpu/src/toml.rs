@@ -15,7 +15,8 @@ pub struct Toml {
     /// arguments to `cargo gpu build` are listed.
     ///
     /// Path arguments like `output-dir` and `shader-manifest` must be relative to
-    /// the location of the Cargo.toml file.
+    /// the location of the Cargo.toml file. Arguments that can be given more than
+    /// once, like `entry-point`, take an array.
     ///
     /// Example:
     ///
@@ -34,12 +35,26 @@ pub struct Toml {
     /// `.spv` files and manifest in a directory "shaders".
     #[clap(default_value = "./Cargo.toml", verbatim_doc_comment)]
     path: std::path::PathBuf,
+
+    /// Print a JSON Schema of the `[*.metadata.rust-gpu]` table instead of building, so that
+    /// editors can complete and validate it.
+    #[clap(long)]
+    emit_json_schema: bool,
+
+    /// Only check that the build described by the toml file could start, as with
+    /// `cargo gpu build --check-only`.
+    #[clap(long)]
+    check_only: bool,
 }
 
 impl Toml {
     /// Entrypoint
     pub fn run(&self) -> anyhow::Result<()> {
-        let (path, toml) = Self::parse_cargo_toml(self.path.clone())?;
+        if self.emit_json_schema {
+            return Self::print_json_schema();
+        }
+
+        let (path, toml) = Self::parse_cargo_toml(&self.path)?;
         let working_directory = path
             .parent()
             .context("Couldn't find parent for shader's `Cargo.toml`")?;
@@ -82,19 +97,26 @@ impl Toml {
         );
         log::debug!("table: {table:#?}");
 
+        Self::warn_on_workspace_mismatch(toml_type, working_directory, &table);
+
         log::info!(
             "issuing cargo commands from the working directory '{}'",
             working_directory.display()
         );
         std::env::set_current_dir(working_directory)?;
 
-        let parameters = construct_build_parameters_from_toml_table(toml_type, &table)?;
+        let mut parameters = construct_build_parameters_from_toml_table(toml_type, &table)?;
+        if self.check_only {
+            parameters.push("--check-only".to_owned());
+        }
         log::debug!("build parameters: {parameters:#?}");
         if let Cli {
             command: Command::Build(mut build),
+            ..
         } = Cli::parse_from(parameters)
         {
             log::debug!("build: {build:?}");
+            crate::set_offline_env(&build.install);
             build.run()?;
         } else {
             log::error!("parameters found in [{toml_type}.metadata.rust-gpu.build] were not parameters to `cargo gpu build`");
@@ -104,22 +126,22 @@ impl Toml {
         Ok(())
     }
 
+    /// Print the [`metadata_json_schema`].
+    #[expect(
+        clippy::print_stdout,
+        reason = "The schema is intended to be redirected to a file"
+    )]
+    fn print_json_schema() -> anyhow::Result<()> {
+        println!("{}", serde_json::to_string_pretty(&metadata_json_schema())?);
+        Ok(())
+    }
+
     /// Parse the contents of the shader's `Cargo.toml`
     pub fn parse_cargo_toml(
-        mut path: std::path::PathBuf,
+        path: &std::path::Path,
     ) -> anyhow::Result<(std::path::PathBuf, toml::Table)> {
         // Find the path to the toml file to use
-        let parsed_path = if path.is_file() && path.ends_with(".toml") {
-            path
-        } else {
-            path = path.join("Cargo.toml");
-            if path.is_file() {
-                path
-            } else {
-                log::error!("toml file '{}' is not a file", path.display());
-                anyhow::bail!("toml file '{}' is not a file", path.display());
-            }
-        };
+        let parsed_path = crate::cargo_manifest_path(path)?;
 
         log::info!("using toml file '{}'", parsed_path.display());
 
@@ -129,6 +151,104 @@ impl Toml {
         Ok((parsed_path, toml))
     }
 
+    /// Nested workspaces can make it surprising which `[*.metadata.rust-gpu]` table is applied
+    /// to a shader crate, so warn when the shader crate's actual workspace isn't the one we expect.
+    ///
+    /// Returns the keys of the workspace's table that are reported as ignored, when a
+    /// package's own table is applied instead.
+    fn warn_on_workspace_mismatch(
+        toml_type: &str,
+        toml_directory: &std::path::Path,
+        table: &toml::Table,
+    ) -> Vec<String> {
+        let shader_crate = table
+            .get("build")
+            .and_then(|build| build.get("shader-crate"))
+            .or_else(|| table.get("shader-crate"))
+            .and_then(toml::Value::as_str)
+            .map_or_else(
+                || toml_directory.to_path_buf(),
+                |shader_crate| toml_directory.join(shader_crate),
+            );
+        let workspace_root = match crate::cargo_workspace_root(&shader_crate)
+            .and_then(|root| Ok(root.canonicalize()?))
+        {
+            Ok(workspace_root) => workspace_root,
+            Err(error) => {
+                log::debug!(
+                    "could not check the workspace of '{}': {error}",
+                    shader_crate.display()
+                );
+                return Vec::new();
+            }
+        };
+        if workspace_root == toml_directory {
+            return Vec::new();
+        }
+
+        if toml_type == "workspace" {
+            log::warn!(
+                "applying [workspace.metadata.rust-gpu] from the workspace at '{}', but the \
+                 shader crate '{}' belongs to the workspace at '{}'",
+                toml_directory.display(),
+                shader_crate.display(),
+                workspace_root.display()
+            );
+            return Vec::new();
+        }
+
+        let ignored_keys = Self::parse_cargo_toml(&workspace_root)
+            .ok()
+            .and_then(|(_, toml)| {
+                let workspace_table = Self::get_metadata_rustgpu_table(&toml, "workspace")?;
+                Some(Self::ignored_workspace_keys(
+                    Some(table),
+                    workspace_table,
+                    "",
+                ))
+            })
+            .unwrap_or_default();
+        if !ignored_keys.is_empty() {
+            log::warn!(
+                "applying [package.metadata.rust-gpu] from the package at '{}', and ignoring \
+                 `{}` of [workspace.metadata.rust-gpu] from its workspace at '{}'",
+                toml_directory.display(),
+                ignored_keys.join("`, `"),
+                workspace_root.display()
+            );
+        }
+        ignored_keys
+    }
+
+    /// The keys of a `[workspace.metadata.rust-gpu]` table, as dotted paths below `prefix` like
+    /// `build.output-dir`, whose values the package's table doesn't have. The package's table
+    /// always names its own shader crate, so `shader-crate` isn't compared.
+    fn ignored_workspace_keys(
+        package: Option<&toml::Table>,
+        workspace: &toml::Table,
+        prefix: &str,
+    ) -> Vec<String> {
+        workspace
+            .iter()
+            .filter(|(key, _)| *key != "shader-crate")
+            .flat_map(|(key, value)| {
+                let path = format!("{prefix}{key}");
+                let package_value = package.and_then(|table| table.get(key));
+                if let toml::Value::Table(workspace_table) = value {
+                    Self::ignored_workspace_keys(
+                        package_value.and_then(toml::Value::as_table),
+                        workspace_table,
+                        &format!("{path}."),
+                    )
+                } else if package_value == Some(value) {
+                    Vec::new()
+                } else {
+                    vec![path]
+                }
+            })
+            .collect()
+    }
+
     /// Parse the `[package.metadata.rust-gpu]` section.
     fn get_metadata_rustgpu_table<'toml>(
         toml: &'toml toml::Table,
@@ -140,6 +260,126 @@ impl Toml {
     }
 }
 
+/// A JSON Schema of the `[*.metadata.rust-gpu]` table. Its `build` table holds the arguments to
+/// `cargo gpu build`, so the schema is derived from their command line definitions.
+fn metadata_json_schema() -> serde_json::Value {
+    let build_properties: serde_json::Map<String, serde_json::Value> =
+        <crate::build::Build as clap::CommandFactory>::command()
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| {
+                let long = arg.get_long()?;
+                if long == "help" {
+                    return None;
+                }
+
+                let mut property = if matches!(
+                    arg.get_action(),
+                    clap::ArgAction::SetTrue | clap::ArgAction::SetFalse
+                ) {
+                    let mut boolean = serde_json::Map::new();
+                    boolean.insert("type".to_owned(), "boolean".into());
+                    boolean
+                } else if matches!(arg.get_action(), clap::ArgAction::Append) {
+                    let mut array = serde_json::Map::new();
+                    array.insert("type".to_owned(), "array".into());
+                    array.insert("items".to_owned(), value_json_schema(arg).into());
+                    array
+                } else {
+                    let mut value = value_json_schema(arg);
+                    if let [default] = arg.get_default_values() {
+                        let default_string = default.to_string_lossy();
+                        let default_value = if is_integer_arg(arg) {
+                            default_string.parse::<i64>().map_or_else(
+                                |_| default_string.as_ref().into(),
+                                serde_json::Value::from,
+                            )
+                        } else {
+                            default_string.as_ref().into()
+                        };
+                        value.insert("default".to_owned(), default_value);
+                    }
+                    value
+                };
+                if let Some(help) = arg.get_long_help().or_else(|| arg.get_help()) {
+                    property.insert("description".to_owned(), help.to_string().into());
+                }
+                Some((long.to_owned(), property.into()))
+            })
+            .collect();
+
+    serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "[*.metadata.rust-gpu]",
+        "description": "Configuration for `cargo gpu toml`",
+        "type": "object",
+        "properties": {
+            "build": {
+                "description": "Arguments to `cargo gpu build`",
+                "type": "object",
+                "properties": build_properties,
+                "additionalProperties": false,
+            },
+        },
+    })
+}
+
+/// Whether the argument's values are parsed as integers, and so are TOML integers.
+fn is_integer_arg(arg: &clap::Arg) -> bool {
+    let type_id = arg.get_value_parser().type_id();
+    [
+        core::any::TypeId::of::<u8>(),
+        core::any::TypeId::of::<u16>(),
+        core::any::TypeId::of::<u32>(),
+        core::any::TypeId::of::<u64>(),
+        core::any::TypeId::of::<usize>(),
+        core::any::TypeId::of::<i32>(),
+        core::any::TypeId::of::<i64>(),
+    ]
+    .iter()
+    .any(|integer| type_id == *integer)
+}
+
+/// The JSON Schema of a single value of an argument, an integer or otherwise a string, which is
+/// one of its possible values if it has any.
+fn value_json_schema(arg: &clap::Arg) -> serde_json::Map<String, serde_json::Value> {
+    let mut value = serde_json::Map::new();
+    if is_integer_arg(arg) {
+        value.insert("type".to_owned(), "integer".into());
+        return value;
+    }
+    value.insert("type".to_owned(), "string".into());
+    let possible_values: Vec<serde_json::Value> = arg
+        .get_possible_values()
+        .iter()
+        .map(|possible_value| possible_value.get_name().into())
+        .collect();
+    if !possible_values.is_empty() {
+        value.insert("enum".to_owned(), possible_values.into());
+    }
+    value
+}
+
+/// Whether the argument's `CARGO_GPU_*` environment variable is set, which takes precedence
+/// over the value in the TOML table.
+fn is_overridden_by_env(build_command: &clap::Command, key: &str) -> bool {
+    let maybe_env = build_command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(key))
+        .and_then(clap::Arg::get_env);
+    let Some(env) = maybe_env else {
+        return false;
+    };
+    let is_set = std::env::var_os(env).is_some();
+    if is_set {
+        log::info!(
+            "using `{}` from the environment rather than `{key}` from the toml file",
+            env.to_string_lossy()
+        );
+    }
+    is_set
+}
+
 /// Construct the cli parameters to run a `cargo gpu build` command from a TOML table.
 fn construct_build_parameters_from_toml_table(
     toml_type: &str,
@@ -152,11 +392,12 @@ fn construct_build_parameters_from_toml_table(
         .with_context(|| {
             format!("toml file's '{toml_type}.metadata.rust-gpu.build' property is not a table")
         })?;
+    let build_command = <crate::build::Build as clap::CommandFactory>::command();
     let mut parameters: Vec<String> = build_table
         .into_iter()
+        .filter(|(key, _)| !is_overridden_by_env(&build_command, key))
         .map(|(key, val)| -> anyhow::Result<Vec<String>> {
             Ok(match val {
-                toml::Value::String(string) => vec![format!("--{key}"), string.clone()],
                 toml::Value::Boolean(truthy) => {
                     if *truthy {
                         vec![format!("--{key}")]
@@ -164,16 +405,17 @@ fn construct_build_parameters_from_toml_table(
                         vec![]
                     }
                 }
-                toml::Value::Integer(_)
+                // Arguments that can be given more than once take an array, one per value.
+                toml::Value::Array(values) => values
+                    .iter()
+                    .map(|value| Ok(vec![format!("--{key}"), toml_value_to_arg(value)?]))
+                    .collect::<anyhow::Result<Vec<Vec<String>>>>()?
+                    .concat(),
+                toml::Value::String(_)
+                | toml::Value::Integer(_)
                 | toml::Value::Float(_)
                 | toml::Value::Datetime(_)
-                | toml::Value::Array(_)
-                | toml::Value::Table(_) => {
-                    let mut value = String::new();
-                    let ser = toml::ser::ValueSerializer::new(&mut value);
-                    serde::Serialize::serialize(val, ser)?;
-                    vec![format!("--{key}"), value]
-                }
+                | toml::Value::Table(_) => vec![format!("--{key}"), toml_value_to_arg(val)?],
             })
         })
         .collect::<anyhow::Result<Vec<Vec<String>>>>()?
@@ -184,3 +426,200 @@ fn construct_build_parameters_from_toml_table(
     parameters.insert(1, "build".to_owned());
     Ok(parameters)
 }
+
+/// A single TOML value as a command line argument: strings as they are, and anything else as
+/// TOML.
+fn toml_value_to_arg(value: &toml::Value) -> anyhow::Result<String> {
+    if let toml::Value::String(string) = value {
+        return Ok(string.clone());
+    }
+    let mut arg = String::new();
+    let ser = toml::ser::ValueSerializer::new(&mut arg);
+    serde::Serialize::serialize(value, ser)?;
+    Ok(arg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn metadata_json_schema_describes_build_arguments() {
+        let schema = metadata_json_schema();
+        let property = |name: &str, field: &str| {
+            schema
+                .pointer(&format!("/properties/build/properties/{name}/{field}"))
+                .cloned()
+        };
+        assert_eq!(
+            Some(serde_json::json!("boolean")),
+            property("no-default-features", "type")
+        );
+        assert_eq!(
+            Some(serde_json::json!("string")),
+            property("output-dir", "type")
+        );
+        assert_eq!(
+            Some(serde_json::json!("./")),
+            property("output-dir", "default")
+        );
+        assert_eq!(
+            Some(serde_json::json!([
+                "linkage",
+                "shader-module",
+                "map",
+                "linkage-map"
+            ])),
+            property("manifest-schema", "enum")
+        );
+        assert!(property("shader-crate", "type").is_some());
+        assert!(property("help", "type").is_none());
+        assert_eq!(Some(serde_json::json!("integer")), property("jobs", "type"));
+        assert_eq!(
+            Some(serde_json::json!("array")),
+            property("entry-point", "type")
+        );
+        assert_eq!(
+            Some(serde_json::json!({ "type": "string" })),
+            property("entry-point", "items")
+        );
+    }
+
+    #[test_log::test]
+    fn reporting_ignored_workspace_keys() {
+        let workspace = crate::test::fixture_dir("disagreeing-workspace");
+        let shader = workspace.join("shader");
+        std::fs::create_dir_all(shader.join("src")).unwrap();
+        std::fs::write(
+            workspace.join("Cargo.toml"),
+            r#"
+            [workspace]
+            members = ["shader"]
+            resolver = "2"
+
+            [workspace.metadata.rust-gpu.build]
+            shader-crate = "shader"
+            output-dir = "workspace-shaders"
+            jobs = 2
+            capabilities = ["Int8"]
+
+            [workspace.metadata.rust-gpu.build.spirv-builder]
+            rev = "82a0f69"
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            shader.join("Cargo.toml"),
+            r#"
+            [package]
+            name = "shader"
+            version = "0.0.0"
+            edition = "2021"
+
+            [package.metadata.rust-gpu.build]
+            output-dir = "shaders"
+            jobs = 2
+            "#,
+        )
+        .unwrap();
+        std::fs::write(shader.join("src").join("lib.rs"), "").unwrap();
+        let shader_dir = shader.canonicalize().unwrap();
+        let (_, toml) = Toml::parse_cargo_toml(&shader_dir).unwrap();
+        let mut table = Toml::get_metadata_rustgpu_table(&toml, "package")
+            .unwrap()
+            .clone();
+        table.insert(
+            "shader-crate".to_owned(),
+            shader_dir.display().to_string().into(),
+        );
+
+        let mut keys = Toml::warn_on_workspace_mismatch("package", &shader_dir, &table);
+        keys.sort();
+        assert_eq!(
+            vec![
+                "build.capabilities",
+                "build.output-dir",
+                "build.spirv-builder.rev"
+            ],
+            keys
+        );
+        crate::test::tests_teardown();
+    }
+
+    /// Check `table` against the `build` properties of [`metadata_json_schema`], as far as
+    /// the types that it uses go.
+    fn validate_build_table(table: &toml::Table) -> Result<(), String> {
+        let schema = metadata_json_schema();
+        let matches = |property: &serde_json::Value, value: &toml::Value| {
+            matches!(
+                (
+                    property.get("type").and_then(serde_json::Value::as_str),
+                    value
+                ),
+                (Some("boolean"), toml::Value::Boolean(_))
+                    | (Some("integer"), toml::Value::Integer(_))
+                    | (Some("string"), toml::Value::String(_))
+            )
+        };
+        for (key, value) in table {
+            let property = schema
+                .pointer(&format!("/properties/build/properties/{key}"))
+                .ok_or_else(|| format!("`{key}` isn't a build argument"))?;
+            let is_valid = match value {
+                toml::Value::Array(items) => {
+                    property.get("type") == Some(&serde_json::json!("array"))
+                        && property
+                            .get("items")
+                            .is_some_and(|item| items.iter().all(|each| matches(item, each)))
+                }
+                toml::Value::String(_)
+                | toml::Value::Integer(_)
+                | toml::Value::Float(_)
+                | toml::Value::Boolean(_)
+                | toml::Value::Datetime(_)
+                | toml::Value::Table(_) => matches(property, value),
+            };
+            if !is_valid {
+                return Err(format!("`{key} = {value}` doesn't match {property}"));
+            }
+        }
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn metadata_json_schema_validates_integers_and_arrays() {
+        let build_table: toml::Table = toml::from_str(
+            r#"
+            jobs = 2
+            entry-point = ["main_vs", "main_fs"]
+            capabilities = ["Int8"]
+            no-default-features = true
+            "#,
+        )
+        .unwrap();
+        validate_build_table(&build_table).unwrap();
+        for invalid in [
+            r#"jobs = "2""#,
+            r#"entry-point = "main_vs""#,
+            "capabilities = [8]",
+        ] {
+            let table: toml::Table = toml::from_str(invalid).unwrap();
+            validate_build_table(&table).unwrap_err();
+        }
+
+        let mut metadata = toml::Table::new();
+        metadata.insert("build".to_owned(), build_table.into());
+        let parameters = construct_build_parameters_from_toml_table("package", &metadata).unwrap();
+        if let crate::Cli {
+            command: crate::Command::Build(build),
+            ..
+        } = <crate::Cli as clap::Parser>::parse_from(parameters)
+        {
+            assert_eq!(Some(2), build.install.jobs);
+            assert_eq!(vec!["main_vs", "main_fs"], build.entry_point_filter);
+            assert_eq!(vec![spirv::Capability::Int8], build.capabilities);
+        } else {
+            panic!("was not a build command");
+        }
+    }
+}
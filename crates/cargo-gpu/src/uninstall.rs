@@ -0,0 +1,84 @@
+//! `cargo gpu uninstall`, which removes a single cached `spirv-builder-cli` installation so that
+//! the next build installs it afresh.
+
+use anyhow::Context as _;
+
+use crate::{install::Install, spirv_source::SpirvSource};
+
+/// `cargo gpu uninstall`
+#[derive(clap::Parser, Debug)]
+pub struct Uninstall {
+    /// The shader crate and `rust-gpu` source whose installation to remove. As with
+    /// `cargo gpu install`, the source and toolchain are read from the shader crate unless
+    /// they're given explicitly.
+    #[clap(flatten)]
+    pub install: Install,
+
+    /// Remove the installation without asking for confirmation, eg in scripts.
+    #[clap(long, short)]
+    yes: bool,
+}
+
+impl Uninstall {
+    /// Entrypoint
+    pub fn run(&self) -> anyhow::Result<()> {
+        let spirv_cli = self.install.spirv_cli(self.install.shader_crate())?;
+        ensure_is_removable(&spirv_cli.source)?;
+
+        let dir = spirv_cli.checkout_path()?;
+        if !dir.exists() {
+            crate::user_output!(
+                "There is no installation for {spirv_cli} at {}\n",
+                dir.display()
+            );
+            return Ok(());
+        }
+
+        if !self.yes
+            && !crate::spirv_cli::ask_for_consent(&format!(
+                "Remove the installation for {spirv_cli} at {}?",
+                dir.display()
+            ))?
+        {
+            crate::user_output!("Not removing anything\n");
+            return Ok(());
+        }
+
+        std::fs::remove_dir_all(&dir)
+            .with_context(|| format!("could not remove installation '{}'", dir.display()))?;
+        crate::user_output!("Removed the installation at {}\n", dir.display());
+        Ok(())
+    }
+}
+
+/// Refuse to remove the installations of local `rust-gpu` checkouts, which the user is
+/// presumably working on.
+fn ensure_is_removable(source: &SpirvSource) -> anyhow::Result<()> {
+    if let SpirvSource::Path((path, _)) = source {
+        anyhow::bail!(
+            "the `rust-gpu` source is the local checkout '{path}', which `cargo gpu uninstall` \
+             doesn't remove installations of"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn only_remote_sources_are_removable() {
+        ensure_is_removable(&SpirvSource::CratesIO("0.9.0".to_owned())).unwrap();
+        ensure_is_removable(&SpirvSource::Git {
+            url: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+            rev: "82a0f69".to_owned(),
+        })
+        .unwrap();
+        ensure_is_removable(&SpirvSource::Path((
+            "/rust-gpu".to_owned(),
+            "0.9.0".to_owned(),
+        )))
+        .unwrap_err();
+    }
+}
@@ -0,0 +1,15 @@
+//! Generates code used by the shader entry points.
+//!
+//! This also makes sure that `cargo gpu build` runs the build scripts of shader crates, which
+//! are compiled for the host rather than for the SPIR-V target.
+
+fn main() {
+    let out_dir = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    std::fs::write(
+        out_dir.join("generated.rs"),
+        "/// The number of vertices in the quad, generated by `build.rs`.\n\
+         pub const QUAD_VERTEX_COUNT: usize = 6;\n",
+    )
+    .unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+}
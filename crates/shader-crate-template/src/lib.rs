@@ -5,7 +5,9 @@
 use glam::{Vec2, Vec4};
 use spirv_std::spirv;
 
-pub const CLIP_SPACE_COORD_QUAD_CCW: [Vec4; 6] = {
+include!(concat!(env!("OUT_DIR"), "/generated.rs"));
+
+pub const CLIP_SPACE_COORD_QUAD_CCW: [Vec4; QUAD_VERTEX_COUNT] = {
     let tl = Vec4::new(-1.0, 1.0, 0.5, 1.0);
     let tr = Vec4::new(1.0, 1.0, 0.5, 1.0);
     let bl = Vec4::new(-1.0, -1.0, 0.5, 1.0);
@@ -13,7 +15,7 @@ pub const CLIP_SPACE_COORD_QUAD_CCW: [Vec4; 6] = {
     [bl, br, tr, tr, tl, bl]
 };
 
-pub const UV_COORD_QUAD_CCW: [Vec2; 6] = {
+pub const UV_COORD_QUAD_CCW: [Vec2; QUAD_VERTEX_COUNT] = {
     let tl = Vec2::new(0.0, 0.0);
     let tr = Vec2::new(1.0, 0.0);
     let bl = Vec2::new(0.0, 1.0);
@@ -28,7 +30,7 @@ pub fn vertex(
     out_uv: &mut Vec2,
     #[spirv(position)] clip_pos: &mut Vec4,
 ) {
-    let index = vertex_id as usize % 6;
+    let index = vertex_id as usize % QUAD_VERTEX_COUNT;
     *out_uv = UV_COORD_QUAD_CCW[index];
     *clip_pos = CLIP_SPACE_COORD_QUAD_CCW[index];
 }
@@ -6,6 +6,15 @@ pub struct Linkage {
     pub source_path: String,
     pub entry_point: String,
     pub wgsl_entry_point: String,
+    /// The SPIR-V capabilities declared by the entry point's module, if they were reflected.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+    /// The target that the entry point's module was compiled for, if it was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// The local workgroup size of a compute entry point, if it was reflected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workgroup_size: Option<[u32; 3]>,
 }
 
 impl Linkage {
@@ -20,9 +29,30 @@ impl Linkage {
                 .join("/"),
             wgsl_entry_point: entry_point.as_ref().replace("::", ""),
             entry_point: entry_point.as_ref().to_string(),
+            capabilities: Vec::new(),
+            target: None,
+            workgroup_size: None,
         }
     }
 
+    /// Mangle the entry point's module path into the `wgsl_entry_point` with `separator` in
+    /// place of each `::`, rather than removing them.
+    pub fn with_wgsl_separator(mut self, separator: &str) -> Self {
+        self.wgsl_entry_point = self.entry_point.replace("::", separator);
+        self
+    }
+
+    /// Write the `source_path` with `separator` between its components, rather than the
+    /// portable forward slash, for tools that expect their platform's native paths.
+    pub fn with_path_separator(mut self, separator: &str) -> Self {
+        self.source_path = self
+            .source_path
+            .split('/')
+            .collect::<Vec<_>>()
+            .join(separator);
+        self
+    }
+
     pub fn fn_name(&self) -> &str {
         self.entry_point.split("::").last().unwrap()
     }
@@ -51,6 +81,14 @@ pub struct Args {
 
     /// Path to the output directory for the compiled shaders.
     pub output_dir: std::path::PathBuf,
+
+    /// Names of extra SPIR-V capabilities to enable.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+
+    /// Compile all entry points into a single module, rather than one module per entry point.
+    #[serde(default)]
+    pub single_module: bool,
 }
 
 /// A built shader entry-point, used in `spirv-builder-cli` to generate
@@ -59,6 +59,8 @@ fn main() {
         no_default_features,
         features,
         output_dir,
+        capabilities,
+        single_module,
     } = args;
 
     let CompileResult {
@@ -67,7 +69,7 @@ fn main() {
     } = {
         let mut builder = SpirvBuilder::new(shader_crate, &shader_target)
             .print_metadata(MetadataPrintout::None)
-            .multimodule(true);
+            .multimodule(!single_module);
 
         #[cfg(feature = "spirv-builder-pre-cli")]
         {
@@ -90,6 +92,14 @@ fn main() {
             }
         }
 
+        for capability in capabilities {
+            log::info!("enabling capability {capability}");
+            let capability = capability
+                .parse()
+                .unwrap_or_else(|()| panic!("unknown capability {capability}"));
+            builder = builder.capability(capability);
+        }
+
         log::debug!("Calling `rust-gpu`'s `spirv-builder` library");
         builder.build().unwrap()
     };